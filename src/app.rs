@@ -1,242 +1,395 @@
+mod background;
+mod compare;
+mod compare_modes;
 mod config;
+mod culling;
+mod directory;
+mod file_operations;
+mod filmstrip;
 mod gui;
-mod image_utils;
+pub mod image_utils;
 mod input;
+mod rating;
+mod raw;
+mod session;
 mod state;
-mod utils;
+pub mod utils;
+mod watcher;
+mod window;
+mod xmp;
+mod zoom;
 
+pub use window::WindowMode;
+
+use compare::CompareState;
+use compare_modes::CompareMode;
 use config::AppConfig;
-use image_utils::{ImageInfo, ImageState, get_image_info};
+use file_operations::rename_group;
+use filmstrip::Filmstrip;
+use image_utils::raw::{RawDecode, load_raw_preview};
+use image_utils::{ImageInfo, ImageState, copy_text_to_clipboard, get_image_info};
+use session::Session;
 use state::AppState;
-use utils::{calculate_initial_window_size, path_as_uri};
+use utils::{calculate_initial_window_size, window_title};
+use watcher::FileWatcher;
 
 use eframe::CreationContext;
-use egui::{CentralPanel, ColorImage, Context, Frame, Pos2, Rect, Vec2, load::SizedTexture};
+use egui::{CentralPanel, Context, Frame, Pos2, Rect, Vec2, ViewportCommand};
 use egui_notify::Toasts;
+use image::RgbaImage;
 
-use rayon::prelude::*;
 use rfd::FileDialog;
 
-use std::{path::PathBuf, time::Duration};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-const SUPPORTED_EXTENSIONS: [&str; 42] = [
+pub const SUPPORTED_EXTENSIONS: [&str; 42] = [
     "avif", "bmp", "dds", "ff", "gif", "hdr", "ico", "jpeg", "jpg", "exr", "png", "pnm", "qoi",
     "svg", "tga", "tiff", "webp", "heif", "jxl", "mrw", "arw", "srf", "sr2", "mef", "orf", "srw",
     "erf", "kdc", "dcs", "rw2", "raf", "dcr", "dng", "pef", "crw", "iiq", "3fr", "nrw", "nef",
     "mos", "cr2", "ari",
 ];
+// Camera RAW formats among the supported ones, they are decoded through ImageMagick
+pub const RAW_EXTENSIONS: [&str; 23] = [
+    "mrw", "arw", "srf", "sr2", "mef", "orf", "srw", "erf", "kdc", "dcs", "rw2", "raf", "dcr",
+    "dng", "pef", "crw", "iiq", "3fr", "nrw", "nef", "mos", "cr2", "ari",
+];
 struct App {
     app_state: AppState,
     image_state: ImageState,
+    compare: CompareState,
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
         CentralPanel::default()
-            .frame(Frame::new().fill(self.app_state.background_color))
+            .frame(Frame::new().fill(self.background_fill()))
             .show(ctx, |ui| {
-                self.app_state.window_size = ui.available_size();
+                self.app_state.pixels_per_point = ctx.pixels_per_point();
 
-                self.handle_input(ui, ctx);
+                let pane_rects = self.compare.pane_rects(ui.max_rect());
+                self.focus_hovered_pane(ui, &pane_rects);
+                self.set_viewport(pane_rects[self.compare.active]);
 
-                if !self.image_state.info.path.exists() && !self.open_image() {
-                    std::process::exit(0);
+                self.handle_input(ctx);
+                self.handle_file_events(ctx);
+                self.poll_full_decodes(ctx);
+
+                match self.image_state.info.path.exists() {
+                    true => self.app_state.missing_since = None,
+                    false => self.handle_missing_image(ctx),
                 }
 
-                if self.image_state.color_image.is_some() {
-                    self.image_state.texture_handle = Some(ctx.load_texture(
-                        &self.image_state.uri,
-                        self.image_state.color_image.take().unwrap(),
-                        Default::default(),
-                    ));
-
-                    self.image_state.sized_texture = Some(SizedTexture::from_handle(
-                        self.image_state.texture_handle.as_mut().unwrap(),
-                    ));
-                    self.image_state.color_image = None;
+                self.update_session(ctx);
+                self.sync_linked_panes();
+
+                // Panes may have been opened or closed by the input
+                let pane_rects = self.compare.pane_rects(ui.max_rect());
+                let has_image = !self.image_state.info.path.as_os_str().is_empty();
+                if !has_image {
+                    self.render_start_screen(ui);
+                } else if self.compare.is_overlaid() {
+                    self.render_compare_mode(ui, ui.max_rect());
+                } else {
+                    for (index, pane_rect) in pane_rects.iter().enumerate() {
+                        self.render_pane(ui, index, *pane_rect);
+                    }
+                    self.render_pane_borders(ui, &pane_rects);
                 }
+                self.set_viewport(pane_rects[self.compare.active]);
 
-                self.render_img(ui);
+                if self.app_state.show_filmstrip && has_image {
+                    self.render_filmstrip(ui, ui.max_rect());
+                }
 
                 if let Some(uri) = &self.image_state.uri_to_forget {
                     ctx.forget_image(uri);
                 }
 
-                if self.app_state.show_info {
-                    self.render_info(ui);
+                if self.app_state.rename_buffer.is_some() {
+                    self.render_rename_dialog(ctx);
                 }
 
+                if self.app_state.culling_summary.is_some() {
+                    self.render_culling_summary(ctx);
+                }
+
+                if self.app_state.show_raw_development {
+                    self.render_raw_development(ctx);
+                }
+
+                self.update_cursor_visibility(ctx);
+
                 self.app_state.toasts.show(ctx); // Show all notifications
             });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_session();
+    }
 }
 
 impl App {
-    pub fn new(cc: &CreationContext<'_>, img_info: ImageInfo, color_image: ColorImage) -> Self {
+    pub fn new(
+        cc: &CreationContext<'_>,
+        img_info: ImageInfo,
+        pixels: RgbaImage,
+        compare_paths: Vec<PathBuf>,
+        session: Session,
+    ) -> Self {
         let cfg = AppConfig::default();
 
         cc.egui_ctx.set_zoom_factor(cfg.default_ui_scale);
 
-        let image_state = ImageState {
-            info: img_info.clone(),
-
-            uri: path_as_uri(&img_info.path),
-            uri_to_forget: None,
-
-            rotation: 0,
-            zoom_factor: 1.0,
-
-            uv_rect: Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
-            offset: Vec2::ZERO,
-
-            color_image: Some(color_image),
-            texture_handle: None,
-            sized_texture: None,
-        };
+        let zoom_mode = session.zoom_mode.unwrap_or(cfg.zoom_mode);
+        let mut image_state = ImageState::new(img_info, pixels, zoom_mode);
+        image_state.raw_decode = cfg.raw_decode;
+        image_state.raw_development = cfg.raw_development;
+        if image_state.info.embedded_preview && cfg.raw_decode == RawDecode::Full {
+            image_state.start_full_decode(&image_state.info.path.clone());
+        }
 
         let app_state = AppState {
             window_size: Vec2::ZERO,
+            viewport: Rect::ZERO,
+            pixels_per_point: cc.egui_ctx.pixels_per_point(),
+            max_zoom_percent: cfg.max_zoom_percent,
+            nearest_filter_zoom_percent: cfg.nearest_filter_zoom_percent,
+            pixel_grid_min_cell_size: cfg.pixel_grid_min_cell_size,
             background_color: cfg.background_color,
+            background_mode: session.background_mode.unwrap_or(cfg.background_mode),
+            checkerboard_size: cfg.checkerboard_size,
+            checkerboard_colors: cfg.checkerboard_colors,
+            checkerboard_texture: None,
 
             maintain_aspect_ratio: cfg.maintain_aspect_ratio,
             show_info: cfg.show_info,
+            show_pixel_inspector: cfg.show_pixel_inspector,
+            show_histogram: cfg.show_histogram,
+            show_pixel_grid: cfg.show_pixel_grid,
+            show_filmstrip: cfg.show_filmstrip,
+            histogram_log_scale: cfg.histogram_log_scale,
             dragging: false,
 
+            rename_buffer: None,
+
+            target_directories: cfg.target_directories,
+            file_operations: Vec::new(),
+
+            filmstrip: Filmstrip::new(),
+            session,
+
+            ratings: HashMap::new(),
+            rating_filter: None,
+            culling_summary: None,
+
+            group_raw_pairs: cfg.group_raw_pairs,
+            show_raw_in_pairs: false,
+            sidecar_naming: cfg.sidecar_naming,
+            embed_xmp: cfg.embed_xmp,
+            show_raw_development: false,
+            raw_development: cfg.raw_development,
+
+            cursor_hide_delay: cfg
+                .hide_cursor_in_fullscreen
+                .then(|| Duration::from_millis(cfg.cursor_hide_delay_millis)),
+            last_pointer_activity: Instant::now(),
+
+            file_watcher: FileWatcher::new(&cc.egui_ctx).ok(),
+            directory_listing: None,
+            auto_reload: cfg.auto_reload,
+            pending_reload: None,
+            missing_since: None,
+            reload_attempts: 0,
+            reload_debounce: Duration::from_millis(cfg.reload_debounce_millis),
+            reload_retries: cfg.reload_retries,
+
             toasts: Toasts::default(),
             notification_duration: Option::from(Duration::from_millis(
                 cfg.notification_duration_millis,
             )),
         };
 
-        Self {
+        let mut app = Self {
             app_state,
             image_state,
+            compare: CompareState {
+                panes: vec![None],
+                active: 0,
+                linked: cfg.compare_linked,
+                split: cfg.compare_split,
+
+                mode: CompareMode::SideBySide,
+                alignment: cfg.compare_alignment,
+                amplification: cfg.difference_amplification,
+                blink_interval: Duration::from_millis(cfg.blink_interval_millis),
+                wipe_position: 0.5,
+                cache: None,
+            },
+        };
+
+        for path in &compare_paths {
+            app.open_pane(path);
         }
+        app.focus_pane(0);
+
+        app
     }
 
-    fn open_image(&mut self) -> bool {
-        let file = FileDialog::new()
+    fn pick_image_file(&self) -> Option<PathBuf> {
+        FileDialog::new()
             .set_directory(dirs::home_dir().unwrap_or_default())
             .add_filter("image", &SUPPORTED_EXTENSIONS)
-            .pick_file();
+            .pick_file()
+    }
 
-        if let Some(file) = file {
-            self.image_state.load_new_image(&file).ok();
-            return true;
+    fn open_image(&mut self) {
+        if let Some(file) = self.pick_image_file() {
+            self.open_file(&file);
         }
-
-        false
     }
 
-    fn next_image(&mut self, step: i128) -> Result<(), Box<dyn std::error::Error>> {
-        let current_dir = self.image_state.info.path.parent().unwrap();
-        let mut img_files: Vec<PathBuf> = std::fs::read_dir(current_dir)?
-            .filter_map(|entry| {
-                entry.ok().and_then(|e| {
-                    let path = e.path();
-                    let ext = path
-                        .extension()
-                        .and_then(|e| e.to_str())
-                        .map(|e| e.to_lowercase())
-                        .unwrap_or_default();
-
-                    if SUPPORTED_EXTENSIONS.contains(&ext.as_str()) {
-                        Some(path)
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect();
-
-        img_files.par_sort_by(|a, b| {
-            let a_name = a.file_name().unwrap_or_default();
-            let b_name = b.file_name().unwrap_or_default();
+    fn open_file(&mut self, path: &PathBuf) {
+        if let Err(e) = self.image_state.load_new_image(path) {
+            self.app_state
+                .notify(format!("Couldn't open {}: {}", path.to_string_lossy(), e));
+        }
+    }
 
-            match (a_name.to_str(), b_name.to_str()) {
-                (Some(a_str), Some(b_str)) => a_str.cmp(b_str),
-                _ => a_name.cmp(b_name), // Compare as OsStr, may cause some issues =P
-            }
-        });
-
-        let current_file_index = img_files
-            .par_iter()
-            .position_any(|f| f == &self.image_state.info.path)
-            .unwrap() as i128;
-        let max_index = img_files.len() as i128 - 1;
-
-        let mut new_file_index = current_file_index + step;
-        let mut broken_indexes: Vec<i128> = Vec::new();
-        loop {
-            if broken_indexes.contains(&new_file_index) {
+    fn rename_image(&mut self, ctx: &Context, new_name: &str) {
+        let old_path = self.image_state.info.path.clone();
+        let group = self.file_group(&old_path);
+
+        let result = self
+            .image_state
+            .rename_target(new_name)
+            .and_then(|new_path| {
+                if new_path != old_path {
+                    rename_group(&old_path, &new_path, &group)?;
+                    self.image_state.set_path(new_path);
+                }
+                Ok(())
+            });
+        match result {
+            Ok(()) => {
+                ctx.send_viewport_cmd(ViewportCommand::Title(window_title(
+                    &self.image_state.info.name,
+                )));
                 self.app_state
-                    .notify(String::from("Couldn't open any image in this directory"))
+                    .notify(format!("Renamed to {}", self.image_state.info.name));
             }
+            Err(e) => self.app_state.notify(format!("Couldn't rename: {}", e)),
+        }
+    }
 
-            new_file_index = {
-                if new_file_index == -1 {
-                    max_index
-                } else if new_file_index > max_index {
-                    0
-                } else {
-                    new_file_index
-                }
-            };
+    fn copy_color_under_pointer(&mut self, pointer: Option<Pos2>) {
+        let color = pointer
+            .and_then(|p| self.pixel_under_pointer(p))
+            .and_then(|(x, y)| self.image_state.pixel(x, y));
 
-            let new_file_pathbuf = img_files.get(new_file_index as usize).unwrap();
-            if self.image_state.load_new_image(new_file_pathbuf)? {
-                return Ok(());
+        match color {
+            Some([r, g, b, _]) => {
+                let hex = format!("#{:02X}{:02X}{:02X}", r, g, b);
+                copy_text_to_clipboard(&hex);
+                self.app_state
+                    .notify(format!("Color {} was copied to clipboard", hex));
             }
-
-            self.app_state.notify(format!(
-                "Couldn't open {}",
-                new_file_pathbuf.to_str().unwrap()
-            ));
-
-            broken_indexes.push(new_file_index);
-            new_file_index += step;
+            None => self
+                .app_state
+                .notify(String::from("No pixel under the cursor")),
         }
     }
 }
 
-pub fn run(img_path: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+pub fn run(
+    img_path: Option<PathBuf>,
+    compare_paths: Vec<PathBuf>,
+    window_mode: WindowMode,
+    restore: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let initial_window_size: Vec2;
     let img_info: ImageInfo;
-    let color_image: ColorImage;
+    let pixels: RgbaImage;
+
+    let session = Session::load();
+
+    // Without a file to size the window after, it comes back as it was left
+    let saved_geometry = restore || img_path.is_none();
+    let saved_window_size = session
+        .window_size
+        .filter(|_| saved_geometry && window_mode != WindowMode::Fullscreen)
+        .map(Vec2::from);
+    let saved_window_position = session
+        .window_position
+        .filter(|_| saved_geometry && window_mode != WindowMode::Fullscreen)
+        .map(Pos2::from);
+
+    let img_path = match restore {
+        true => img_path.or(session.last_file.clone().filter(|path| path.exists())),
+        false => img_path,
+    };
 
     if let Some(img_path) = img_path {
-        (img_info, color_image) = get_image_info(&img_path)?;
-        initial_window_size = calculate_initial_window_size(&img_info);
+        // A RAW file opens with its embedded preview, App::new starts the full decode
+        (img_info, pixels) = match load_raw_preview(&img_path) {
+            Some(preview) => preview,
+            None => get_image_info(&img_path)?,
+        };
+        initial_window_size = saved_window_size
+            .unwrap_or_else(|| calculate_initial_window_size(&img_info, window_mode));
     } else {
-        initial_window_size = Vec2::new(600.0, 600.0);
+        initial_window_size = match window_mode {
+            WindowMode::Fullscreen => {
+                calculate_initial_window_size(&ImageInfo::default(), window_mode)
+            }
+            _ => saved_window_size.unwrap_or(Vec2::new(600.0, 600.0)),
+        };
         img_info = ImageInfo::default();
-        color_image = ColorImage::default();
+        pixels = RgbaImage::default();
+    }
+
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_app_id("ru.arabianq.aqiv")
+        .with_inner_size(initial_window_size)
+        .with_min_inner_size(Vec2::new(200.0, 200.0))
+        .with_fullscreen(window_mode == WindowMode::Fullscreen)
+        .with_decorations(window_mode != WindowMode::Borderless);
+    if let Some(position) = saved_window_position {
+        viewport = viewport.with_position(position);
     }
 
     let options = eframe::NativeOptions {
         vsync: true,
-        centered: true,
+        centered: saved_window_position.is_none(),
         hardware_acceleration: eframe::HardwareAcceleration::Preferred,
 
-        viewport: egui::ViewportBuilder::default()
-            .with_app_id("ru.arabianq.aqiv")
-            .with_inner_size(initial_window_size)
-            .with_min_inner_size(Vec2::new(200.0, 200.0)),
+        viewport,
 
         ..Default::default()
     };
 
     match eframe::run_native(
-        format!("Quick Image Viewer - {}", img_info.name).as_str(),
+        window_title(&img_info.name).as_str(),
         options,
         Box::new(|cc| {
             cc.egui_ctx.options_mut(|options| {
-                options.zoom_with_keyboard = true;
+                // UI scale is handled in handle_input, egui's own Ctrl+0 would
+                // reset it while copying to the tenth target folder
+                options.zoom_with_keyboard = false;
                 options.reduce_texture_memory = true;
             });
 
             egui_material_icons::initialize(&cc.egui_ctx);
-            Ok(Box::new(App::new(cc, img_info, color_image)))
+            Ok(Box::new(App::new(
+                cc,
+                img_info,
+                pixels,
+                compare_paths,
+                session,
+            )))
         }),
     ) {
         Ok(_) => Ok(()),
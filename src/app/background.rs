@@ -0,0 +1,154 @@
+use crate::app::App;
+
+use egui::{Color32, ColorImage, Pos2, Rect, TextureOptions, Ui};
+use image::{RgbaImage, imageops};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+const BLURRED_BACKDROP_SIZE: u32 = 64;
+const BLURRED_BACKDROP_SIGMA: f32 = 3.0;
+const BLURRED_BACKDROP_BRIGHTNESS: f32 = 0.4;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundMode {
+    Solid,
+    Checkerboard,
+    White,
+    Black,
+    Blurred,
+}
+
+impl BackgroundMode {
+    pub const ALL: [BackgroundMode; 5] = [
+        BackgroundMode::Solid,
+        BackgroundMode::Checkerboard,
+        BackgroundMode::White,
+        BackgroundMode::Black,
+        BackgroundMode::Blurred,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|m| *m == self)
+            .unwrap_or_default();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for BackgroundMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BackgroundMode::Solid => "Solid",
+            BackgroundMode::Checkerboard => "Checkerboard",
+            BackgroundMode::White => "White",
+            BackgroundMode::Black => "Black",
+            BackgroundMode::Blurred => "Blurred image",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn blurred_backdrop(pixels: &RgbaImage) -> ColorImage {
+    let (width, height) = pixels.dimensions();
+    let scale = BLURRED_BACKDROP_SIZE as f32 / width.max(height).max(1) as f32;
+    let small = imageops::thumbnail(
+        pixels,
+        ((width as f32 * scale) as u32).max(1),
+        ((height as f32 * scale) as u32).max(1),
+    );
+    let blurred = imageops::blur(&small, BLURRED_BACKDROP_SIGMA);
+
+    let darkened: Vec<Color32> = blurred
+        .as_raw()
+        .par_chunks_exact(4)
+        .map(|p| {
+            let darken = |v: u8| (v as f32 * BLURRED_BACKDROP_BRIGHTNESS) as u8;
+            Color32::from_rgb(darken(p[0]), darken(p[1]), darken(p[2]))
+        })
+        .collect();
+
+    ColorImage::new(
+        [blurred.width() as usize, blurred.height() as usize],
+        darkened,
+    )
+}
+
+impl App {
+    pub fn background_fill(&self) -> Color32 {
+        match self.app_state.background_mode {
+            BackgroundMode::Solid | BackgroundMode::Checkerboard => self.app_state.background_color,
+            BackgroundMode::White => Color32::WHITE,
+            BackgroundMode::Black | BackgroundMode::Blurred => Color32::BLACK,
+        }
+    }
+
+    pub fn cycle_background_mode(&mut self) {
+        self.set_background_mode(self.app_state.background_mode.next());
+    }
+
+    pub fn set_background_mode(&mut self, mode: BackgroundMode) {
+        self.app_state.background_mode = mode;
+        self.app_state.notify(format!("Background: {}", mode));
+    }
+
+    pub fn render_background(&mut self, ui: &mut Ui) {
+        match self.app_state.background_mode {
+            BackgroundMode::Checkerboard => self.render_checkerboard(ui),
+            BackgroundMode::Blurred => self.render_blurred_backdrop(ui),
+            _ => (),
+        }
+    }
+
+    fn render_checkerboard(&mut self, ui: &mut Ui) {
+        let Some(screen_rect) = self.calculate_screen_rect() else {
+            return;
+        };
+
+        // 2x2 texture repeated over the image, one texel per cell
+        let [light, dark] = self.app_state.checkerboard_colors;
+        let texture = self.app_state.checkerboard_texture.get_or_insert_with(|| {
+            ui.ctx().load_texture(
+                "checkerboard",
+                ColorImage::new([2, 2], vec![light, dark, dark, light]),
+                TextureOptions::NEAREST_REPEAT,
+            )
+        });
+
+        let cells = screen_rect.size() / (2.0 * self.app_state.checkerboard_size);
+        let uv = Rect::from_min_max(Pos2::ZERO, cells.to_pos2());
+
+        ui.painter()
+            .image(texture.id(), screen_rect, uv, Color32::WHITE);
+    }
+
+    fn render_blurred_backdrop(&mut self, ui: &mut Ui) {
+        let pixels = &self.image_state.pixels;
+        if pixels.width() == 0 || pixels.height() == 0 {
+            return;
+        }
+
+        let texture = self.image_state.backdrop_texture.get_or_insert_with(|| {
+            ui.ctx().load_texture(
+                format!("{}#backdrop", self.image_state.uri),
+                blurred_backdrop(pixels),
+                TextureOptions::LINEAR,
+            )
+        });
+
+        // Cover the whole pane, cropping whatever doesn't fit
+        let viewport = self.app_state.viewport;
+        let texture_size = texture.size_vec2();
+        let scale = (viewport.size() / texture_size).max_elem();
+        let backdrop_rect = Rect::from_center_size(viewport.center(), texture_size * scale);
+
+        ui.painter().image(
+            texture.id(),
+            backdrop_rect,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use crate::app::App;
+use crate::app::compare_modes::{Alignment, CompareCache, CompareMode};
+use crate::app::directory::is_raw_image;
+use crate::app::image_utils::raw::RawDevelopment;
+use crate::app::image_utils::{ImageState, get_image_info};
+
+use egui::{Color32, Popup, Pos2, Rect, Stroke, StrokeKind, Ui, UiBuilder, Vec2};
+
+use std::{fmt, path::Path, time::Duration};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SplitDirection {
+    // Panes side by side
+    Vertical,
+    // Panes stacked on top of each other
+    Horizontal,
+}
+
+impl SplitDirection {
+    pub fn next(self) -> Self {
+        match self {
+            SplitDirection::Vertical => SplitDirection::Horizontal,
+            SplitDirection::Horizontal => SplitDirection::Vertical,
+        }
+    }
+}
+
+impl fmt::Display for SplitDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            SplitDirection::Vertical => "Vertical",
+            SplitDirection::Horizontal => "Horizontal",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Every pane has its own ImageState. The active one lives in App::image_state,
+// so its slot here is empty, the others are swapped in while they are drawn
+pub struct CompareState {
+    pub panes: Vec<Option<ImageState>>,
+    pub active: usize,
+    pub linked: bool,
+    pub split: SplitDirection,
+
+    pub mode: CompareMode,
+    pub alignment: Alignment,
+    pub amplification: f32,
+    pub blink_interval: Duration,
+    // Divider position in 0..1 along the split direction
+    pub wipe_position: f32,
+    pub cache: Option<CompareCache>,
+}
+
+impl CompareState {
+    pub fn is_split(&self) -> bool {
+        self.panes.len() > 1
+    }
+
+    // Difference, blink and wipe draw the panes over each other
+    pub fn is_overlaid(&self) -> bool {
+        self.is_split() && self.mode != CompareMode::SideBySide
+    }
+
+    pub fn pane_rects(&self, full_rect: Rect) -> Vec<Rect> {
+        if self.is_overlaid() {
+            return vec![full_rect; self.panes.len()];
+        }
+
+        let count = self.panes.len() as f32;
+
+        (0..self.panes.len())
+            .map(|index| {
+                let index = index as f32;
+                match self.split {
+                    SplitDirection::Vertical => {
+                        let width = full_rect.width() / count;
+                        Rect::from_min_size(
+                            Pos2::new(full_rect.left() + width * index, full_rect.top()),
+                            Vec2::new(width, full_rect.height()),
+                        )
+                    }
+                    SplitDirection::Horizontal => {
+                        let height = full_rect.height() / count;
+                        Rect::from_min_size(
+                            Pos2::new(full_rect.left(), full_rect.top() + height * index),
+                            Vec2::new(full_rect.width(), height),
+                        )
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+impl App {
+    pub fn set_viewport(&mut self, viewport: Rect) {
+        self.app_state.viewport = viewport;
+        self.app_state.window_size = viewport.size();
+    }
+
+    pub fn focus_pane(&mut self, index: usize) {
+        let compare = &mut self.compare;
+        if index == compare.active || index >= compare.panes.len() {
+            return;
+        }
+
+        let Some(pane) = compare.panes[index].take() else {
+            return;
+        };
+        compare.panes[compare.active] = Some(std::mem::replace(&mut self.image_state, pane));
+        compare.active = index;
+    }
+
+    // Runs f with the pane at index swapped into image_state
+    pub fn with_pane(&mut self, index: usize, f: impl FnOnce(&mut Self)) {
+        if index == self.compare.active {
+            f(self);
+            return;
+        }
+
+        let Some(mut pane) = self.compare.panes[index].take() else {
+            return;
+        };
+        std::mem::swap(&mut self.image_state, &mut pane);
+        f(self);
+        std::mem::swap(&mut self.image_state, &mut pane);
+        self.compare.panes[index] = Some(pane);
+    }
+
+    pub fn open_pane(&mut self, path: &Path) {
+        match get_image_info(&path.to_path_buf()) {
+            Ok((info, pixels)) => {
+                let mut pane = ImageState::new(info, pixels, self.image_state.zoom_mode);
+                pane.raw_decode = self.image_state.raw_decode;
+                pane.raw_development = self.image_state.raw_development;
+                pane.keep_raw_base = self.image_state.keep_raw_base;
+                // The pane was decoded as is, adjustments need a develop of their own
+                if is_raw_image(path) && pane.raw_development != RawDevelopment::default() {
+                    pane.start_full_decode(path);
+                }
+                if self.compare.linked {
+                    copy_view(&self.image_state, &mut pane);
+                }
+
+                self.compare.panes.push(Some(pane));
+                self.focus_pane(self.compare.panes.len() - 1);
+            }
+            Err(e) => self.app_state.notify(format!("Couldn't open image: {}", e)),
+        }
+    }
+
+    pub fn open_pane_dialog(&mut self) {
+        if let Some(file) = self.pick_image_file() {
+            self.open_pane(&file);
+        }
+    }
+
+    pub fn close_active_pane(&mut self) {
+        if !self.compare.is_split() {
+            return;
+        }
+
+        let closed = self.compare.active;
+        let neighbour = match closed {
+            0 => 1,
+            _ => closed - 1,
+        };
+        self.focus_pane(neighbour);
+        self.compare.panes.remove(closed);
+        if self.compare.active > closed {
+            self.compare.active -= 1;
+        }
+        self.compare.cache = None;
+    }
+
+    pub fn toggle_linked_view(&mut self) {
+        self.compare.linked = !self.compare.linked;
+        self.app_state
+            .notify(format!("Linked view: {}", self.compare.linked));
+    }
+
+    pub fn cycle_split_direction(&mut self) {
+        self.compare.split = self.compare.split.next();
+        self.app_state
+            .notify(format!("Split: {}", self.compare.split));
+    }
+
+    // Zoom, offset, rotation and flips of the active pane are copied to the others
+    pub fn sync_linked_panes(&mut self) {
+        if !self.compare.linked {
+            return;
+        }
+
+        for pane in self.compare.panes.iter_mut().flatten() {
+            copy_view(&self.image_state, pane);
+        }
+    }
+
+    // Pane under the pointer becomes active, unless something is being dragged
+    // or a menu of the active pane is open
+    pub fn focus_hovered_pane(&mut self, ui: &Ui, pane_rects: &[Rect]) {
+        if !self.compare.is_split()
+            || self.compare.is_overlaid()
+            || self.app_state.dragging
+            || Popup::is_any_open(ui.ctx())
+        {
+            return;
+        }
+
+        let Some(pointer) = ui.ctx().input(|i| i.pointer.hover_pos()) else {
+            return;
+        };
+
+        if let Some(index) = pane_rects.iter().position(|rect| rect.contains(pointer)) {
+            self.focus_pane(index);
+        }
+    }
+
+    pub fn render_pane(&mut self, ui: &mut Ui, index: usize, pane_rect: Rect) {
+        self.with_pane(index, |app| {
+            app.set_viewport(pane_rect);
+
+            ui.scope_builder(UiBuilder::new().max_rect(pane_rect), |ui| {
+                ui.set_clip_rect(pane_rect);
+
+                app.apply_zoom_mode();
+                app.update_texture_filter();
+
+                app.render_background(ui);
+                app.render_img(ui);
+                app.render_image_overlays(ui);
+            });
+        });
+    }
+
+    pub fn render_image_overlays(&mut self, ui: &mut Ui) {
+        if self.app_state.show_info {
+            self.render_info(ui);
+        }
+
+        if self.app_state.show_pixel_grid {
+            self.render_pixel_grid(ui);
+        }
+
+        if self.app_state.show_histogram {
+            self.render_histogram(ui);
+        }
+
+        if self.app_state.show_pixel_inspector {
+            self.render_pixel_inspector(ui);
+        }
+    }
+
+    pub fn focus_next_pane(&mut self) {
+        if self.compare.is_split() {
+            self.focus_pane((self.compare.active + 1) % self.compare.panes.len());
+        }
+    }
+
+    pub fn render_pane_borders(&self, ui: &Ui, pane_rects: &[Rect]) {
+        if !self.compare.is_split() || self.compare.is_overlaid() {
+            return;
+        }
+
+        let painter = ui.painter();
+        for (index, rect) in pane_rects.iter().enumerate() {
+            let stroke = match index == self.compare.active {
+                true => Stroke::new(2.0, ui.visuals().selection.bg_fill),
+                false => Stroke::new(1.0, Color32::from_black_alpha(160)),
+            };
+            painter.rect_stroke(*rect, 0.0, stroke, StrokeKind::Inside);
+        }
+    }
+}
+
+pub fn copy_view(from: &ImageState, to: &mut ImageState) {
+    to.zoom_factor = from.zoom_factor;
+    to.zoom_mode = from.zoom_mode;
+    to.offset = from.offset;
+    to.rotation = from.rotation;
+    to.uv_rect = from.uv_rect;
+}
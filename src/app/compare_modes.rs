@@ -0,0 +1,369 @@
+use crate::app::App;
+use crate::app::compare::{SplitDirection, copy_view};
+use crate::app::image_utils::ImageState;
+
+use egui::{Align2, Color32, CursorIcon, FontId, Pos2, Rect, Sense, Stroke, Ui, UiBuilder};
+use image::{RgbaImage, imageops};
+use rayon::prelude::*;
+
+use std::{fmt, time::Duration};
+
+const MAX_DIFFERENCE_AMPLIFICATION: f32 = 64.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CompareMode {
+    SideBySide,
+    Difference,
+    Blink,
+    Wipe,
+}
+
+impl CompareMode {
+    pub const ALL: [CompareMode; 4] = [
+        CompareMode::SideBySide,
+        CompareMode::Difference,
+        CompareMode::Blink,
+        CompareMode::Wipe,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|m| *m == self)
+            .unwrap_or_default();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for CompareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            CompareMode::SideBySide => "Side by side",
+            CompareMode::Difference => "Difference",
+            CompareMode::Blink => "Blink",
+            CompareMode::Wipe => "Wipe",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// How the second image is placed over the first one when their resolutions differ
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alignment {
+    TopLeft,
+    ScaleToFit,
+}
+
+impl Alignment {
+    pub fn next(self) -> Self {
+        match self {
+            Alignment::TopLeft => Alignment::ScaleToFit,
+            Alignment::ScaleToFit => Alignment::TopLeft,
+        }
+    }
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Alignment::TopLeft => "Top left",
+            Alignment::ScaleToFit => "Scale to fit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Second image redrawn onto the canvas of the first one, so both share the same geometry
+pub struct CompareCache {
+    // Generations of both images, addresses could be reused by the next image
+    key: (u64, u64, Alignment),
+    aligned: ImageState,
+    difference: Option<(f32, ImageState)>,
+}
+
+pub fn align_image(pixels: &RgbaImage, width: u32, height: u32, alignment: Alignment) -> RgbaImage {
+    if pixels.dimensions() == (width, height) {
+        return pixels.clone();
+    }
+
+    let mut canvas = RgbaImage::new(width, height);
+    match alignment {
+        Alignment::TopLeft => imageops::replace(&mut canvas, pixels, 0, 0),
+        Alignment::ScaleToFit => {
+            let scale =
+                (width as f32 / pixels.width() as f32).min(height as f32 / pixels.height() as f32);
+            let scaled_width = ((pixels.width() as f32 * scale).round() as u32).clamp(1, width);
+            let scaled_height = ((pixels.height() as f32 * scale).round() as u32).clamp(1, height);
+
+            let scaled = imageops::resize(
+                pixels,
+                scaled_width,
+                scaled_height,
+                imageops::FilterType::Triangle,
+            );
+            imageops::replace(
+                &mut canvas,
+                &scaled,
+                ((width - scaled_width) / 2) as i64,
+                ((height - scaled_height) / 2) as i64,
+            );
+        }
+    }
+
+    canvas
+}
+
+// Absolute per-channel difference, amplified so small encoder errors become visible
+pub fn difference_image(a: &RgbaImage, b: &RgbaImage, amplification: f32) -> RgbaImage {
+    let pixels: Vec<u8> = a
+        .as_raw()
+        .par_chunks_exact(4)
+        .zip(b.as_raw().par_chunks_exact(4))
+        .flat_map_iter(|(a, b)| {
+            let alpha_difference = a[3].abs_diff(b[3]);
+            let amplify = |i: usize| {
+                let difference = a[i].abs_diff(b[i]).max(alpha_difference);
+                (difference as f32 * amplification).min(255.0) as u8
+            };
+            [amplify(0), amplify(1), amplify(2), 255]
+        })
+        .collect();
+
+    RgbaImage::from_raw(a.width(), a.height(), pixels).unwrap()
+}
+
+impl App {
+    pub fn set_compare_mode(&mut self, mode: CompareMode) {
+        if mode != CompareMode::SideBySide && !self.compare.is_split() {
+            self.app_state.notify(String::from(
+                "Open a second image with Shift + O to compare",
+            ));
+            return;
+        }
+
+        self.compare.mode = mode;
+        self.app_state.notify(format!("Compare: {}", mode));
+    }
+
+    pub fn cycle_compare_mode(&mut self) {
+        self.set_compare_mode(self.compare.mode.next());
+    }
+
+    pub fn cycle_alignment(&mut self) {
+        self.compare.alignment = self.compare.alignment.next();
+        self.app_state
+            .notify(format!("Alignment: {}", self.compare.alignment));
+    }
+
+    pub fn scale_difference_amplification(&mut self, factor: f32) {
+        self.compare.amplification =
+            (self.compare.amplification * factor).clamp(1.0, MAX_DIFFERENCE_AMPLIFICATION);
+        self.app_state.notify(format!(
+            "Difference amplification: {}x",
+            self.compare.amplification
+        ));
+    }
+
+    fn update_compare_cache(&mut self, other: usize) {
+        let Some(Some(other_state)) = self.compare.panes.get(other) else {
+            return;
+        };
+
+        let pixels = &self.image_state.pixels;
+        let key = (
+            self.image_state.generation,
+            other_state.generation,
+            self.compare.alignment,
+        );
+
+        let mut cache = match self.compare.cache.take() {
+            Some(cache) if cache.key == key => cache,
+            _ => {
+                let aligned = align_image(
+                    &other_state.pixels,
+                    pixels.width(),
+                    pixels.height(),
+                    self.compare.alignment,
+                );
+                CompareCache {
+                    key,
+                    aligned: ImageState::new(
+                        other_state.info.clone(),
+                        aligned,
+                        other_state.zoom_mode,
+                    ),
+                    difference: None,
+                }
+            }
+        };
+
+        let amplification = self.compare.amplification;
+        let outdated = !matches!(cache.difference, Some((a, _)) if a == amplification);
+        if self.compare.mode == CompareMode::Difference && outdated {
+            let difference = difference_image(pixels, &cache.aligned.pixels, amplification);
+            cache.difference = Some((
+                amplification,
+                ImageState::new(
+                    self.image_state.info.clone(),
+                    difference,
+                    self.image_state.zoom_mode,
+                ),
+            ));
+        }
+
+        self.compare.cache = Some(cache);
+    }
+
+    // Paints the aligned second image (or the difference) with the view of the active pane
+    fn paint_cached_image(&mut self, ui: &mut Ui, difference: bool) {
+        let Some(mut cache) = self.compare.cache.take() else {
+            return;
+        };
+
+        let state = match difference {
+            true => cache.difference.as_mut().map(|(_, state)| state),
+            false => Some(&mut cache.aligned),
+        };
+
+        if let Some(state) = state {
+            copy_view(&self.image_state, state);
+            state.set_channel(self.image_state.channel);
+
+            std::mem::swap(&mut self.image_state, state);
+            self.update_texture_filter();
+            self.paint_image_tiles(ui);
+            std::mem::swap(&mut self.image_state, state);
+        }
+
+        self.compare.cache = Some(cache);
+    }
+
+    pub fn render_compare_mode(&mut self, ui: &mut Ui, rect: Rect) {
+        let other = (self.compare.active + 1) % self.compare.panes.len();
+        self.update_compare_cache(other);
+
+        self.set_viewport(rect);
+        self.apply_zoom_mode();
+        self.update_texture_filter();
+
+        self.render_background(ui);
+        self.handle_image_interaction(ui);
+
+        match self.compare.mode {
+            CompareMode::SideBySide => self.paint_image_tiles(ui),
+            CompareMode::Difference => self.paint_cached_image(ui, true),
+            CompareMode::Blink => {
+                let interval = self.compare.blink_interval.as_secs_f64().max(0.05);
+                let time = ui.input(|i| i.time);
+                ui.ctx()
+                    .request_repaint_after(Duration::from_secs_f64(interval - time % interval));
+
+                let show_other = (time / interval) as u64 % 2 == 1;
+                let name = match show_other {
+                    true => {
+                        self.paint_cached_image(ui, false);
+                        self.compare.panes[other]
+                            .as_ref()
+                            .map(|p| p.info.name.clone())
+                    }
+                    false => {
+                        self.paint_image_tiles(ui);
+                        Some(self.image_state.info.name.clone())
+                    }
+                };
+
+                ui.painter().text(
+                    Pos2::new(rect.center().x, rect.top() + 10.0),
+                    Align2::CENTER_TOP,
+                    name.unwrap_or_default(),
+                    FontId::proportional(16.0),
+                    Color32::WHITE,
+                );
+            }
+            CompareMode::Wipe => {
+                self.update_wipe_divider(ui, rect);
+                self.paint_image_tiles(ui);
+
+                let wiped_rect = self.wiped_rect(rect);
+                ui.scope_builder(UiBuilder::new().max_rect(rect), |ui| {
+                    ui.set_clip_rect(wiped_rect);
+                    self.paint_cached_image(ui, false);
+                });
+
+                self.paint_wipe_divider(ui, rect);
+            }
+        }
+
+        self.render_image_overlays(ui);
+    }
+
+    // Part of the window that shows the second image
+    fn wiped_rect(&self, rect: Rect) -> Rect {
+        let position = self.compare.wipe_position;
+        match self.compare.split {
+            SplitDirection::Vertical => Rect::from_min_max(
+                Pos2::new(rect.left() + rect.width() * position, rect.top()),
+                rect.right_bottom(),
+            ),
+            SplitDirection::Horizontal => Rect::from_min_max(
+                Pos2::new(rect.left(), rect.top() + rect.height() * position),
+                rect.right_bottom(),
+            ),
+        }
+    }
+
+    fn update_wipe_divider(&mut self, ui: &mut Ui, rect: Rect) {
+        let wiped_rect = self.wiped_rect(rect);
+        let (handle_rect, cursor) = match self.compare.split {
+            SplitDirection::Vertical => (
+                Rect::from_center_size(
+                    Pos2::new(wiped_rect.left(), rect.center().y),
+                    egui::vec2(12.0, rect.height()),
+                ),
+                CursorIcon::ResizeHorizontal,
+            ),
+            SplitDirection::Horizontal => (
+                Rect::from_center_size(
+                    Pos2::new(rect.center().x, wiped_rect.top()),
+                    egui::vec2(rect.width(), 12.0),
+                ),
+                CursorIcon::ResizeVertical,
+            ),
+        };
+
+        let response = ui.interact(handle_rect, ui.id().with("wipe_divider"), Sense::drag());
+        if response.hovered() || response.dragged() {
+            ui.ctx().set_cursor_icon(cursor);
+        }
+
+        if response.dragged()
+            && let Some(pointer) = response.interact_pointer_pos()
+        {
+            let position = match self.compare.split {
+                SplitDirection::Vertical => (pointer.x - rect.left()) / rect.width(),
+                SplitDirection::Horizontal => (pointer.y - rect.top()) / rect.height(),
+            };
+            self.compare.wipe_position = position.clamp(0.0, 1.0);
+        }
+    }
+
+    fn paint_wipe_divider(&self, ui: &Ui, rect: Rect) {
+        let wiped_rect = self.wiped_rect(rect);
+        let stroke = Stroke::new(2.0, Color32::WHITE);
+
+        let handle_center = match self.compare.split {
+            SplitDirection::Vertical => {
+                ui.painter()
+                    .vline(wiped_rect.left(), rect.y_range(), stroke);
+                Pos2::new(wiped_rect.left(), rect.center().y)
+            }
+            SplitDirection::Horizontal => {
+                ui.painter().hline(rect.x_range(), wiped_rect.top(), stroke);
+                Pos2::new(rect.center().x, wiped_rect.top())
+            }
+        };
+
+        ui.painter()
+            .circle(handle_center, 8.0, Color32::from_black_alpha(160), stroke);
+    }
+}
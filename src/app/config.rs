@@ -1,25 +1,106 @@
+use crate::app::background::BackgroundMode;
+use crate::app::compare::SplitDirection;
+use crate::app::compare_modes::Alignment;
+use crate::app::file_operations::TARGET_DIRECTORIES_COUNT;
+use crate::app::image_utils::raw::{RawDecode, RawDevelopment};
+use crate::app::xmp::SidecarNaming;
+use crate::app::zoom::ZoomMode;
+
 use egui::Color32;
 
+use std::path::PathBuf;
+
 pub struct AppConfig {
     pub background_color: Color32,
+    pub background_mode: BackgroundMode,
+    pub checkerboard_size: f32,
+    pub checkerboard_colors: [Color32; 2],
 
+    pub zoom_mode: ZoomMode,
+    pub max_zoom_percent: f32,
+    pub nearest_filter_zoom_percent: f32,
+    pub pixel_grid_min_cell_size: f32,
     pub maintain_aspect_ratio: bool,
     pub show_info: bool,
+    pub show_pixel_inspector: bool,
+    pub show_histogram: bool,
+    pub show_pixel_grid: bool,
+    pub show_filmstrip: bool,
+    pub histogram_log_scale: bool,
 
     pub notification_duration_millis: u64,
     pub default_ui_scale: f32,
+    pub hide_cursor_in_fullscreen: bool,
+    pub cursor_hide_delay_millis: u64,
+
+    pub auto_reload: bool,
+    pub reload_debounce_millis: u64,
+    pub reload_retries: u32,
+
+    pub target_directories: [Option<PathBuf>; TARGET_DIRECTORIES_COUNT],
+
+    pub sidecar_naming: SidecarNaming,
+    // Ratings go into the JPEG itself instead of a sidecar, other formats always get a sidecar
+    pub embed_xmp: bool,
+
+    // Same-stem RAW and JPEG files are one entry when navigating
+    pub group_raw_pairs: bool,
+    pub raw_decode: RawDecode,
+    pub raw_development: RawDevelopment,
+
+    pub compare_linked: bool,
+    pub compare_split: SplitDirection,
+    pub compare_alignment: Alignment,
+    pub difference_amplification: f32,
+    pub blink_interval_millis: u64,
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             background_color: Color32::from_hex("#1B1B1B").unwrap(),
+            background_mode: BackgroundMode::Solid,
+            checkerboard_size: 8.0,
+            checkerboard_colors: [
+                Color32::from_hex("#999999").unwrap(),
+                Color32::from_hex("#666666").unwrap(),
+            ],
 
+            zoom_mode: ZoomMode::Fit,
+            max_zoom_percent: 6400.0,
+            nearest_filter_zoom_percent: 200.0,
+            pixel_grid_min_cell_size: 6.0,
             maintain_aspect_ratio: true,
             show_info: false,
+            show_pixel_inspector: false,
+            show_histogram: false,
+            show_pixel_grid: false,
+            show_filmstrip: false,
+            histogram_log_scale: false,
 
             notification_duration_millis: 500,
             default_ui_scale: 1.25,
+            hide_cursor_in_fullscreen: true,
+            cursor_hide_delay_millis: 2000,
+
+            auto_reload: true,
+            reload_debounce_millis: 300,
+            reload_retries: 5,
+
+            target_directories: Default::default(),
+
+            sidecar_naming: SidecarNaming::FullName,
+            embed_xmp: false,
+
+            group_raw_pairs: false,
+            raw_decode: RawDecode::Full,
+            raw_development: RawDevelopment::default(),
+
+            compare_linked: true,
+            compare_split: SplitDirection::Vertical,
+            compare_alignment: Alignment::ScaleToFit,
+            difference_amplification: 4.0,
+            blink_interval_millis: 500,
         }
     }
 }
@@ -0,0 +1,200 @@
+use crate::app::App;
+use crate::app::file_operations::{move_with_sidecars, unique_destination};
+use crate::app::rating::Flag;
+use crate::app::xmp::own_sidecars;
+
+use egui::{Context, Id, Modal, RichText, ScrollArea, Ui};
+use egui_material_icons::icons;
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+pub const REJECTED_DIRECTORY: &str = "_rejected";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RejectDestination {
+    Directory,
+    Trash,
+}
+
+// Flagged images of the current directory, collected when the summary is opened
+pub struct CullingSummary {
+    pub picks: Vec<PathBuf>,
+    pub rejects: Vec<PathBuf>,
+}
+
+impl App {
+    pub fn toggle_culling_summary(&mut self) {
+        if self.app_state.culling_summary.is_some() {
+            self.app_state.culling_summary = None;
+            return;
+        }
+
+        match self.collect_culling_summary() {
+            Ok(summary) => self.app_state.culling_summary = Some(summary),
+            Err(e) => self
+                .app_state
+                .notify(format!("Couldn't list flagged images: {}", e)),
+        }
+    }
+
+    fn collect_culling_summary(&mut self) -> Result<CullingSummary, Box<dyn Error>> {
+        let files = self.directory_listing()?.files.clone();
+
+        let mut summary = CullingSummary {
+            picks: Vec::new(),
+            rejects: Vec::new(),
+        };
+        // A rejected RAW/JPEG partner may already be gone with the other member
+        for path in files.into_iter().filter(|path| path.exists()) {
+            match self.rating(&path).flag() {
+                Flag::Picked => summary.picks.push(path),
+                Flag::Rejected => summary.rejects.push(path),
+                Flag::Unflagged => (),
+            }
+        }
+
+        Ok(summary)
+    }
+
+    // Sidecars go along, so the reject flag is still there if the image is brought back
+    fn remove_reject(path: &Path, destination: RejectDestination) -> Result<(), Box<dyn Error>> {
+        match destination {
+            RejectDestination::Directory => {
+                let directory = path
+                    .parent()
+                    .ok_or("Image has no directory")?
+                    .join(REJECTED_DIRECTORY);
+                std::fs::create_dir_all(&directory)?;
+
+                let file_name = path.file_name().ok_or("Invalid file name")?;
+                let target = unique_destination(&directory, Path::new(file_name));
+                move_with_sidecars(path, &target)?;
+            }
+            RejectDestination::Trash => {
+                let mut paths = own_sidecars(path, &[]);
+                paths.insert(0, path.to_path_buf());
+                trash::delete_all(paths)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_rejects(&mut self, destination: RejectDestination) {
+        let Some(summary) = &self.app_state.culling_summary else {
+            return;
+        };
+
+        let rejects = summary.rejects.clone();
+        let files: Vec<PathBuf> = rejects
+            .iter()
+            .flat_map(|path| self.file_group(path))
+            .collect();
+
+        let mut removed = 0;
+        // A rejected RAW/JPEG partner may already be gone with the other member
+        for path in files.into_iter().filter(|path| path.exists()) {
+            match Self::remove_reject(&path, destination) {
+                Ok(()) => {
+                    removed += 1;
+                    // The watcher reports it later, next_image below walks the list before that
+                    if let Some(listing) = &mut self.app_state.directory_listing {
+                        listing.remove(&path);
+                    }
+                    self.app_state.ratings.remove(&path);
+                }
+                Err(e) => {
+                    self.app_state
+                        .notify(format!("Couldn't remove {}: {}", path.display(), e))
+                }
+            }
+        }
+
+        self.app_state.notify(match destination {
+            RejectDestination::Directory => {
+                format!("Moved {} files to {}", removed, REJECTED_DIRECTORY)
+            }
+            RejectDestination::Trash => format!("Moved {} files to trash", removed),
+        });
+
+        // Step away from the current image before the watcher reports it missing
+        if !self.image_state.info.path.exists() {
+            self.next_image(1).ok();
+        }
+
+        self.app_state.culling_summary = self.collect_culling_summary().ok();
+    }
+
+    fn render_flagged_list(&mut self, ui: &mut Ui, title: &str, paths: &[PathBuf]) {
+        ui.label(RichText::new(format!("{} ({})", title, paths.len())).strong());
+
+        ScrollArea::vertical()
+            .id_salt(title)
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for path in paths {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let current = *path == self.image_state.info.path;
+                    if ui.selectable_label(current, name).clicked() {
+                        self.open_file(path);
+                    }
+                }
+            });
+    }
+
+    pub fn render_culling_summary(&mut self, ctx: &Context) {
+        let Some(summary) = &self.app_state.culling_summary else {
+            return;
+        };
+        let picks = summary.picks.clone();
+        let rejects = summary.rejects.clone();
+
+        let mut destination = None;
+        let mut closed = false;
+
+        let modal = Modal::new(Id::new("culling_summary")).show(ctx, |ui| {
+            ui.set_width(400.0);
+            ui.label(format!("{} {}", icons::ICON_CHECKLIST, "Picks and rejects"));
+            ui.separator();
+
+            ui.columns(2, |columns| {
+                self.render_flagged_list(&mut columns[0], "Picks", &picks);
+                self.render_flagged_list(&mut columns[1], "Rejects", &rejects);
+            });
+
+            ui.separator();
+            ui.add_enabled_ui(!rejects.is_empty(), |ui| {
+                let move_button = ui.button(format!(
+                    "{} Move rejects to {}/",
+                    icons::ICON_DRIVE_FILE_MOVE,
+                    REJECTED_DIRECTORY
+                ));
+                if move_button.clicked() {
+                    destination = Some(RejectDestination::Directory);
+                }
+
+                let trash_button = ui.button(format!(
+                    "{} {}",
+                    icons::ICON_DELETE,
+                    "Move rejects to trash"
+                ));
+                if trash_button.clicked() {
+                    destination = Some(RejectDestination::Trash);
+                }
+            });
+
+            if ui.button("Close").clicked() {
+                closed = true;
+            }
+        });
+
+        if let Some(destination) = destination {
+            self.remove_rejects(destination);
+        } else if closed || modal.should_close() {
+            self.app_state.culling_summary = None;
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use crate::app::image_utils::{ImageInfo, ImageState};
+use crate::app::{App, RAW_EXTENSIONS, SUPPORTED_EXTENSIONS};
+
+use egui::Context;
+use image::RgbaImage;
+
+use rayon::prelude::*;
+
+use std::{
+    cmp::Ordering,
+    error::Error,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+// Sorted images of one directory, kept in sync by the file watcher
+pub struct DirectoryListing {
+    pub directory: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+
+impl DirectoryListing {
+    pub fn read(directory: &Path) -> std::io::Result<Self> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| is_supported_image(path))
+            .collect();
+
+        files.par_sort_by(|a, b| compare_file_names(a, b));
+
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            files,
+        })
+    }
+
+    // Returns true if the file wasn't listed before
+    pub fn insert(&mut self, path: &Path) -> bool {
+        match self.search(path) {
+            Ok(_) => false,
+            Err(index) => {
+                self.files.insert(index, path.to_path_buf());
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        if let Ok(index) = self.search(path) {
+            self.files.remove(index);
+        }
+    }
+
+    pub fn search(&self, path: &Path) -> Result<usize, usize> {
+        self.files.binary_search_by(|f| compare_file_names(f, path))
+    }
+
+    // All files sharing the stem of path when they include both a RAW and a rendered
+    // image (IMG_0001.CR2 + IMG_0001.JPG), otherwise just path itself
+    pub fn pair_group(&self, path: &Path) -> Vec<PathBuf> {
+        let group: Vec<PathBuf> = self
+            .files
+            .iter()
+            .filter(|file| file.file_stem() == path.file_stem())
+            .cloned()
+            .collect();
+
+        let has_raw = group.iter().any(|file| is_raw_image(file));
+        let has_rendered = group.iter().any(|file| !is_raw_image(file));
+        match has_raw && has_rendered && group.iter().any(|file| file == path) {
+            true => group,
+            false => vec![path.to_path_buf()],
+        }
+    }
+}
+
+pub fn is_supported_image(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    SUPPORTED_EXTENSIONS.contains(&ext.as_str())
+}
+
+pub fn is_raw_image(path: &Path) -> bool {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    RAW_EXTENSIONS.contains(&ext.as_str())
+}
+
+fn compare_file_names(a: &Path, b: &Path) -> Ordering {
+    let a_name = a.file_name().unwrap_or_default();
+    let b_name = b.file_name().unwrap_or_default();
+
+    match (a_name.to_str(), b_name.to_str()) {
+        (Some(a_str), Some(b_str)) => a_str.cmp(b_str),
+        _ => a_name.cmp(b_name), // Compare as OsStr, may cause some issues =P
+    }
+}
+
+impl App {
+    // Without a watcher nothing keeps the cached list fresh, so it's read again every time
+    pub fn directory_listing(&mut self) -> Result<&DirectoryListing, Box<dyn Error>> {
+        let current_dir = self
+            .image_state
+            .info
+            .path
+            .parent()
+            .ok_or("Current image has no directory")?;
+
+        let outdated = match &self.app_state.directory_listing {
+            Some(listing) => {
+                listing.directory != current_dir || self.app_state.file_watcher.is_none()
+            }
+            None => true,
+        };
+        if outdated {
+            self.app_state.directory_listing = Some(DirectoryListing::read(current_dir)?);
+        }
+
+        Ok(self.app_state.directory_listing.as_ref().unwrap())
+    }
+
+    // Applies a watcher event for path to the cached list
+    pub fn update_directory_listing(&mut self, path: &Path) -> bool {
+        let Some(listing) = &mut self.app_state.directory_listing else {
+            return false;
+        };
+        if path.parent() != Some(listing.directory.as_path()) || !is_supported_image(path) {
+            return false;
+        }
+
+        match path.exists() {
+            true => listing.insert(path),
+            false => {
+                listing.remove(path);
+                false
+            }
+        }
+    }
+
+    // With pairs collapsed only the preferred member of each pair is stepped on,
+    // and never a partner of the image that is shown
+    fn is_hidden_pair_member(&self, path: &Path, current_path: &Path) -> bool {
+        let Some(listing) = &self.app_state.directory_listing else {
+            return false;
+        };
+        if !self.app_state.group_raw_pairs {
+            return false;
+        }
+
+        let group = listing.pair_group(path);
+        if group.len() < 2 {
+            return false;
+        }
+
+        group.iter().any(|file| file == current_path)
+            || is_raw_image(path) != self.app_state.show_raw_in_pairs
+    }
+
+    // The files an operation on path applies to, its RAW/JPEG partners included when
+    // pairs are collapsed
+    pub fn file_group(&mut self, path: &Path) -> Vec<PathBuf> {
+        if !self.app_state.group_raw_pairs {
+            return vec![path.to_path_buf()];
+        }
+
+        match self.directory_listing() {
+            Ok(listing) => listing.pair_group(path),
+            Err(_) => vec![path.to_path_buf()],
+        }
+    }
+
+    // Shows the other member of a RAW/JPEG pair, navigation keeps showing that kind
+    pub fn toggle_pair_member(&mut self) {
+        let current_path = self.image_state.info.path.clone();
+        let group = match self.directory_listing() {
+            Ok(listing) => listing.pair_group(&current_path),
+            Err(_) => Vec::new(),
+        };
+
+        let Some(partner) = group
+            .into_iter()
+            .find(|file| is_raw_image(file) != is_raw_image(&current_path))
+        else {
+            self.app_state
+                .notify(String::from("No RAW+JPEG pair for this image"));
+            return;
+        };
+
+        match self.image_state.load_new_image(&partner) {
+            Ok(true) => {
+                self.app_state.show_raw_in_pairs = is_raw_image(&partner);
+                self.app_state
+                    .notify(format!("Showing {}", self.image_state.info.name));
+            }
+            _ => self
+                .app_state
+                .notify(format!("Couldn't open {}", partner.to_string_lossy())),
+        }
+    }
+
+    pub fn toggle_group_raw_pairs(&mut self) {
+        self.app_state.group_raw_pairs = !self.app_state.group_raw_pairs;
+        self.app_state.notify(format!(
+            "Group RAW+JPEG pairs: {}",
+            self.app_state.group_raw_pairs
+        ));
+    }
+
+    pub fn next_image(&mut self, step: i128) -> Result<(), Box<dyn Error>> {
+        let current_path = self.image_state.info.path.clone();
+        let listing = self.directory_listing()?;
+        if listing.files.is_empty() {
+            return Err("No images left in this directory".into());
+        }
+
+        let current_file_index = match listing.search(&current_path) {
+            Ok(index) => index as i128,
+            // Current file was moved away, step from the gap it left
+            Err(index) if step > 0 => index as i128 - 1,
+            Err(index) => index as i128,
+        };
+        let files = listing.files.clone();
+        let files_count = files.len() as i128;
+
+        // Every file gets one try, broken ones are skipped
+        for attempt in 1..=files_count {
+            let new_file_index = (current_file_index + step * attempt).rem_euclid(files_count);
+            let new_file_path = &files[new_file_index as usize];
+
+            if self.is_hidden_pair_member(new_file_path, &current_path)
+                || !self.passes_rating_filter(new_file_path)
+            {
+                continue;
+            }
+
+            if matches!(self.image_state.load_new_image(new_file_path), Ok(true)) {
+                return Ok(());
+            }
+
+            self.app_state
+                .notify(format!("Couldn't open {}", new_file_path.to_string_lossy()));
+        }
+
+        // Nothing was opened, so nothing told the user why
+        if let Some(stars) = self.app_state.rating_filter {
+            self.app_state
+                .notify(format!("No images with {} stars or more", stars));
+        }
+
+        Err("Couldn't open any image in this directory".into())
+    }
+
+    // The current file was deleted or moved by someone else. Editors that save by
+    // deleting and writing anew bring it back, so it gets as long as a reload with all
+    // its retries before moving on. With nothing left the start screen takes its place
+    pub fn handle_missing_image(&mut self, ctx: &Context) {
+        if self.image_state.info.path.as_os_str().is_empty() {
+            return;
+        }
+
+        let missing_since = *self
+            .app_state
+            .missing_since
+            .get_or_insert_with(Instant::now);
+        let grace = self.app_state.reload_debounce * (self.app_state.reload_retries + 1);
+        let elapsed = missing_since.elapsed();
+        if elapsed < grace {
+            ctx.request_repaint_after(grace - elapsed);
+            return;
+        }
+        self.app_state.missing_since = None;
+
+        self.app_state
+            .notify(format!("{} was removed", self.image_state.info.name));
+
+        if self.next_image(1).is_err() {
+            let raw_decode = self.image_state.raw_decode;
+            let raw_development = self.image_state.raw_development;
+            let keep_raw_base = self.image_state.keep_raw_base;
+            self.image_state = ImageState::new(
+                ImageInfo::default(),
+                RgbaImage::default(),
+                self.image_state.zoom_mode,
+            );
+            self.image_state.raw_decode = raw_decode;
+            self.image_state.raw_development = raw_development;
+            self.image_state.keep_raw_base = keep_raw_base;
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use crate::app::App;
+use crate::app::xmp::{SidecarNaming, own_sidecars, shares_base_sidecar};
+
+use egui::Key;
+use rfd::FileDialog;
+
+use std::{
+    error::Error,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+pub const TARGET_DIRECTORIES_COUNT: usize = 10;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TransferMode {
+    Move,
+    Copy,
+}
+
+// One undo step, covering a whole RAW/JPEG group and its sidecars
+pub enum FileOperation {
+    Move { files: Vec<(PathBuf, PathBuf)> },
+    Copy { files: Vec<PathBuf> },
+}
+
+// Slots are bound to number keys 1..9 and 0
+pub fn slot_key_label(slot: usize) -> usize {
+    (slot + 1) % TARGET_DIRECTORIES_COUNT
+}
+
+pub fn target_directory_slot(key: Key) -> Option<usize> {
+    match key {
+        Key::Num1 => Some(0),
+        Key::Num2 => Some(1),
+        Key::Num3 => Some(2),
+        Key::Num4 => Some(3),
+        Key::Num5 => Some(4),
+        Key::Num6 => Some(5),
+        Key::Num7 => Some(6),
+        Key::Num8 => Some(7),
+        Key::Num9 => Some(8),
+        Key::Num0 => Some(9),
+        _ => None,
+    }
+}
+
+pub fn unique_destination(dir: &Path, file_name: &Path) -> PathBuf {
+    let mut destination = dir.join(file_name);
+
+    let stem = file_name.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = file_name.extension().map(|e| e.to_string_lossy());
+
+    let mut counter = 1;
+    while destination.exists() {
+        let new_name = match &ext {
+            Some(ext) => format!("{} ({}).{}", stem, counter, ext),
+            None => format!("{} ({})", stem, counter),
+        };
+        destination = dir.join(new_name);
+        counter += 1;
+    }
+
+    destination
+}
+
+pub fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    // rename() can't cross filesystems, so fall back to copy + remove.
+    // Any other error would happen to the copy as well
+    match std::fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            if let Err(e) = std::fs::copy(from, to) {
+                // Don't leave half a copy behind
+                std::fs::remove_file(to).ok();
+                return Err(e);
+            }
+            std::fs::remove_file(from)
+        }
+        result => result,
+    }
+}
+
+// Sidecars keep their naming scheme, photo.jpg.xmp follows as new.jpg.xmp.
+// A photo.xmp shared with a file that stays behind is left alone.
+// Returns every file that was moved, the image itself first
+pub fn move_sidecars(from: &Path, to: &Path) -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+    let mut moved = Vec::new();
+
+    for naming in [SidecarNaming::FullName, SidecarNaming::BaseName] {
+        if naming == SidecarNaming::BaseName && shares_base_sidecar(from, &[]) {
+            continue;
+        }
+
+        let sidecar = naming.sidecar_path(from);
+        let sidecar_destination = naming.sidecar_path(to);
+        if sidecar.is_file() && !sidecar_destination.exists() {
+            move_file(&sidecar, &sidecar_destination)?;
+            moved.push((sidecar, sidecar_destination));
+        }
+    }
+
+    Ok(moved)
+}
+
+pub fn move_with_sidecars(from: &Path, to: &Path) -> std::io::Result<Vec<(PathBuf, PathBuf)>> {
+    move_file(from, to)?;
+
+    let mut moved = vec![(from.to_path_buf(), to.to_path_buf())];
+    moved.extend(move_sidecars(from, to)?);
+    Ok(moved)
+}
+
+// Returns the created copies, the image itself first
+pub fn copy_with_sidecars(from: &Path, to: &Path) -> std::io::Result<Vec<PathBuf>> {
+    std::fs::copy(from, to)?;
+
+    let mut copies = vec![to.to_path_buf()];
+    for naming in [SidecarNaming::FullName, SidecarNaming::BaseName] {
+        let sidecar = naming.sidecar_path(from);
+        let sidecar_destination = naming.sidecar_path(to);
+        if sidecar.is_file() && !sidecar_destination.exists() {
+            std::fs::copy(&sidecar, &sidecar_destination)?;
+            copies.push(sidecar_destination);
+        }
+    }
+
+    Ok(copies)
+}
+
+// Renames the image with its sidecars, partners take the new stem and keep their own
+// extensions. Every name is checked before anything is renamed, and a failure on the
+// way puts back what was already renamed
+pub fn rename_group(
+    old_path: &Path,
+    new_path: &Path,
+    group: &[PathBuf],
+) -> Result<(), Box<dyn Error>> {
+    let new_stem = new_path.file_stem().ok_or("Invalid file name")?;
+
+    let mut renames = vec![(old_path.to_path_buf(), new_path.to_path_buf())];
+    for partner in group.iter().filter(|file| *file != old_path) {
+        let mut new_name = new_stem.to_os_string();
+        if let Some(ext) = partner.extension() {
+            new_name.push(".");
+            new_name.push(ext);
+        }
+        renames.push((partner.clone(), partner.with_file_name(new_name)));
+    }
+
+    if let Some((_, taken)) = renames.iter().find(|(from, to)| from != to && to.exists()) {
+        return Err(format!("File {} already exists", taken.display()).into());
+    }
+
+    let mut renamed = Vec::new();
+    let result = renames.iter().filter(|(from, to)| from != to).try_for_each(
+        |(from, to)| -> std::io::Result<()> {
+            move_file(from, to)?;
+            renamed.push((from.clone(), to.clone()));
+            renamed.extend(move_sidecars(from, to)?);
+            Ok(())
+        },
+    );
+
+    if let Err(e) = result {
+        for (from, to) in renamed.iter().rev() {
+            move_file(to, from).ok();
+        }
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+impl App {
+    pub fn bind_target_directory(&mut self, slot: usize) {
+        let folder = FileDialog::new()
+            .set_directory(
+                self.image_state
+                    .info
+                    .path
+                    .parent()
+                    .map(Path::to_path_buf)
+                    .unwrap_or_default(),
+            )
+            .pick_folder();
+
+        if let Some(folder) = folder {
+            self.app_state.notify(format!(
+                "Target folder {}: {}",
+                slot_key_label(slot),
+                folder.display()
+            ));
+            self.app_state.target_directories[slot] = Some(folder);
+        }
+    }
+
+    pub fn transfer_image(&mut self, slot: usize, mode: TransferMode) {
+        let Some(target_dir) = self.app_state.target_directories[slot].clone() else {
+            self.app_state.notify(format!(
+                "No target folder bound to {0} (Alt + {0} to bind)",
+                slot_key_label(slot)
+            ));
+            return;
+        };
+
+        match self.try_transfer_image(&target_dir, mode) {
+            Ok(destinations) => {
+                let action = match mode {
+                    TransferMode::Move => "Moved",
+                    TransferMode::Copy => "Copied",
+                };
+                self.app_state.notify(match destinations.as_slice() {
+                    [destination] => format!("{} to {}", action, destination.display()),
+                    _ => format!(
+                        "{} {} files to {}",
+                        action,
+                        destinations.len(),
+                        target_dir.display()
+                    ),
+                });
+            }
+            Err(e) => self.app_state.notify(format!("Couldn't transfer: {}", e)),
+        }
+    }
+
+    // Returns where the images of the group ended up, sidecars aren't counted
+    fn try_transfer_image(
+        &mut self,
+        target_dir: &Path,
+        mode: TransferMode,
+    ) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let source = self.image_state.info.path.clone();
+        // The shown image goes first, undo brings it back on screen
+        let mut group = self.file_group(&source);
+        group.sort_by_key(|file| *file != source);
+
+        let mut destinations = Vec::new();
+        let mut moved = Vec::new();
+        let mut copies = Vec::new();
+        let result = group
+            .iter()
+            .try_for_each(|file| -> Result<(), Box<dyn Error>> {
+                let file_name = file.file_name().ok_or("Invalid file name")?;
+                let destination = unique_destination(target_dir, Path::new(file_name));
+
+                match mode {
+                    TransferMode::Move => moved.extend(move_with_sidecars(file, &destination)?),
+                    TransferMode::Copy => copies.extend(copy_with_sidecars(file, &destination)?),
+                }
+                destinations.push(destination);
+
+                Ok(())
+            });
+
+        // Whatever made it before a failure can still be undone
+        if !moved.is_empty() {
+            self.app_state
+                .file_operations
+                .push(FileOperation::Move { files: moved });
+        }
+        if !copies.is_empty() {
+            self.app_state
+                .file_operations
+                .push(FileOperation::Copy { files: copies });
+        }
+        result?;
+
+        self.next_image(1).ok();
+
+        Ok(destinations)
+    }
+
+    // Moves the image, its partners and sidecars to the trash. The trash has no
+    // common way to restore files, so this can't be undone with Ctrl+Z
+    pub fn trash_image(&mut self) {
+        let path = self.image_state.info.path.clone();
+        let mut files = self.file_group(&path);
+        // A photo.xmp sidecar is shared by the whole group
+        let sidecars: Vec<PathBuf> = files.iter().flat_map(|f| own_sidecars(f, &files)).collect();
+        files.extend(sidecars);
+        files.sort();
+        files.dedup();
+
+        match trash::delete_all(&files) {
+            Ok(()) => {
+                self.app_state
+                    .notify(format!("Moved {} to trash", self.image_state.info.name));
+                self.next_image(1).ok();
+            }
+            Err(e) => self
+                .app_state
+                .notify(format!("Couldn't move to trash: {}", e)),
+        }
+    }
+
+    pub fn undo_file_operation(&mut self) {
+        let Some(operation) = self.app_state.file_operations.pop() else {
+            self.app_state.notify(String::from("Nothing to undo"));
+            return;
+        };
+
+        let result: Result<String, Box<dyn Error>> = match &operation {
+            FileOperation::Move { files } => {
+                if let Some((from, _)) = files.iter().find(|(from, _)| from.exists()) {
+                    Err(format!("{} already exists", from.display()).into())
+                } else {
+                    match files.iter().try_for_each(|(from, to)| move_file(to, from)) {
+                        Ok(()) => {
+                            let (image, _) = &files[0];
+                            self.image_state.load_new_image(image).ok();
+                            Ok(format!("Restored {}", image.display()))
+                        }
+                        Err(e) => Err(e.into()),
+                    }
+                }
+            }
+            FileOperation::Copy { files } => files
+                .iter()
+                .try_for_each(std::fs::remove_file)
+                .map(|_| format!("Removed copy {}", files[0].display()))
+                .map_err(|e| e.into()),
+        };
+
+        match result {
+            Ok(message) => self.app_state.notify(message),
+            Err(e) => {
+                self.app_state.notify(format!("Couldn't undo: {}", e));
+                self.app_state.file_operations.push(operation);
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::app::App;
+use crate::app::image_utils::{
+    PendingDecode, queue_thumbnail,
+    thumbnails::{ThumbnailCache, ThumbnailSize, load_thumbnail_source, make_thumbnail},
+};
+use crate::app::utils::path_as_uri;
+
+use egui::{
+    Align2, Color32, ColorImage, Context, FontId, Pos2, Rect, Sense, Stroke, StrokeKind,
+    TextureHandle, TextureOptions, Ui, Vec2,
+};
+use egui_material_icons::icons;
+use image::RgbaImage;
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, mpsc::TryRecvError},
+};
+
+const FILMSTRIP_HEIGHT: f32 = 96.0;
+const THUMBNAIL_PADDING: f32 = 6.0;
+
+enum Thumbnail {
+    // Dropping it cancels the load, so files that scroll out before their turn are skipped
+    Loading(PendingDecode<Option<RgbaImage>>),
+    // None when the file couldn't be decoded
+    Loaded(Option<TextureHandle>),
+}
+
+// Thumbnails of the neighbours of the current image, loaded in the background
+pub struct Filmstrip {
+    cache: Option<Arc<ThumbnailCache>>,
+    thumbnails: HashMap<PathBuf, Thumbnail>,
+}
+
+impl Filmstrip {
+    pub fn new() -> Self {
+        Self {
+            cache: ThumbnailCache::new().map(Arc::new),
+            thumbnails: HashMap::new(),
+        }
+    }
+
+    fn thumbnail(&mut self, path: &Path, ctx: &Context) -> Option<&TextureHandle> {
+        if !self.thumbnails.contains_key(path) {
+            let path = path.to_path_buf();
+            let cache = self.cache.clone();
+            let ctx = ctx.clone();
+            let pending = queue_thumbnail({
+                let path = path.clone();
+                move || {
+                    let size = ThumbnailSize::Normal;
+                    let thumbnail = match cache {
+                        Some(cache) => cache.get_or_create(&path, size).ok(),
+                        None => load_thumbnail_source(&path)
+                            .ok()
+                            .map(|(_, image)| make_thumbnail(&image, size.pixels())),
+                    };
+                    ctx.request_repaint();
+                    thumbnail
+                }
+            });
+            self.thumbnails.insert(path, Thumbnail::Loading(pending));
+        }
+
+        match self.thumbnails.get(path)? {
+            Thumbnail::Loaded(texture) => texture.as_ref(),
+            Thumbnail::Loading(_) => None,
+        }
+    }
+
+    fn receive_thumbnails(&mut self, ctx: &Context) {
+        for (path, thumbnail) in &mut self.thumbnails {
+            let Thumbnail::Loading(pending) = thumbnail else {
+                continue;
+            };
+            let image = match pending.try_recv() {
+                Ok(image) => image,
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => None,
+            };
+
+            *thumbnail = Thumbnail::Loaded(image.map(|image| {
+                let size = [image.width() as usize, image.height() as usize];
+                ctx.load_texture(
+                    format!("{}#thumbnail", path_as_uri(path)),
+                    ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+                    TextureOptions::LINEAR,
+                )
+            }));
+        }
+    }
+
+    // A rewritten file gets a new thumbnail next time it is shown
+    pub fn forget(&mut self, path: &Path) {
+        self.thumbnails.remove(path);
+    }
+}
+
+impl App {
+    pub fn toggle_show_filmstrip(&mut self) {
+        self.app_state.show_filmstrip = !self.app_state.show_filmstrip;
+        self.app_state
+            .notify(format!("Filmstrip: {}", self.app_state.show_filmstrip));
+    }
+
+    // Strip along the bottom of rect with the current image in the middle,
+    // clicking a thumbnail opens it
+    pub fn render_filmstrip(&mut self, ui: &mut Ui, rect: Rect) {
+        let ctx = ui.ctx().clone();
+        self.app_state.filmstrip.receive_thumbnails(&ctx);
+
+        let current_path = self.image_state.info.path.clone();
+        let Ok(listing) = self.directory_listing() else {
+            return;
+        };
+        let current_index = listing.search(&current_path).unwrap_or_else(|index| index);
+
+        let strip_rect = Rect::from_min_max(
+            Pos2::new(rect.left(), rect.bottom() - FILMSTRIP_HEIGHT),
+            rect.right_bottom(),
+        );
+        let cell_size = FILMSTRIP_HEIGHT;
+        let half_count = (strip_rect.width() / cell_size / 2.0).ceil() as usize;
+
+        let first = current_index.saturating_sub(half_count);
+        let last = (current_index + half_count + 1).min(listing.files.len());
+        let visible: Vec<(usize, PathBuf)> = (first..last)
+            .map(|index| (index, listing.files[index].clone()))
+            .collect();
+
+        let filmstrip = &mut self.app_state.filmstrip;
+        let visible_paths: HashSet<&PathBuf> = visible.iter().map(|(_, path)| path).collect();
+        filmstrip
+            .thumbnails
+            .retain(|path, _| visible_paths.contains(path));
+
+        let painter = ui.painter_at(strip_rect);
+        painter.rect_filled(
+            strip_rect,
+            0.0,
+            self.app_state.background_color.gamma_multiply(0.9),
+        );
+
+        let mut clicked = None;
+        for (index, path) in &visible {
+            let offset = *index as f32 - current_index as f32;
+            let cell_rect = Rect::from_center_size(
+                Pos2::new(
+                    strip_rect.center().x + offset * cell_size,
+                    strip_rect.center().y,
+                ),
+                Vec2::splat(cell_size),
+            )
+            .shrink(THUMBNAIL_PADDING);
+
+            let response =
+                ui.interact(cell_rect, ui.id().with(("filmstrip", path)), Sense::click());
+            if response.clicked() {
+                clicked = Some(path.clone());
+            }
+
+            match filmstrip.thumbnail(path, &ctx) {
+                Some(texture) => {
+                    let image_rect = Rect::from_center_size(
+                        cell_rect.center(),
+                        texture.size_vec2() * (cell_rect.size() / texture.size_vec2()).min_elem(),
+                    );
+                    painter.image(
+                        texture.id(),
+                        image_rect,
+                        Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                        Color32::WHITE,
+                    );
+                }
+                None => {
+                    painter.text(
+                        cell_rect.center(),
+                        Align2::CENTER_CENTER,
+                        icons::ICON_IMAGE,
+                        FontId::proportional(24.0),
+                        Color32::GRAY,
+                    );
+                }
+            }
+
+            let stroke = match *path == current_path {
+                true => Stroke::new(2.0, ui.visuals().selection.bg_fill),
+                false if response.hovered() => Stroke::new(1.0, Color32::GRAY),
+                false => Stroke::NONE,
+            };
+            painter.rect_stroke(cell_rect, 4.0, stroke, StrokeKind::Outside);
+        }
+
+        if let Some(path) = clicked
+            && path != current_path
+        {
+            self.open_file(&path);
+        }
+    }
+}
@@ -1,28 +1,137 @@
 use crate::app::App;
-use crate::app::utils::{calculate_uv_rect, convert_size};
+use crate::app::background::BackgroundMode;
+use crate::app::compare_modes::CompareMode;
+use crate::app::image_utils::raw::{RawDecode, RawDevelopment};
+use crate::app::image_utils::{Channel, HISTOGRAM_BINS};
+use crate::app::rating::Flag;
+use crate::app::utils::{calculate_uv_rect, convert_size, region_to_screen, screen_to_image_pixel};
+use crate::app::zoom::ZoomMode;
 
 use egui::{
-    Align, Color32, Frame, Image, Layout, Pos2, Rect, RichText, Sense, Ui, UiBuilder, Vec2,
+    Align, Color32, Context, Frame, Id, Key, Layout, Mesh, Modal, Pos2, Rect, RichText, Sense,
+    Spinner, TextEdit, Ui, UiBuilder, Vec2, ViewportCommand,
+    emath::Rot2,
+    text::{CCursor, CCursorRange},
+    text_edit::TextEditState,
 };
 use egui_material_icons::icons;
 
 impl App {
     pub fn render_img(&mut self, ui: &mut Ui) {
-        // Creating full screen area to handle dragging
-        let full_area_rect = Rect::from_min_size(
-            Pos2::ZERO,
-            Vec2::new(self.app_state.window_size.x, self.app_state.window_size.y),
-        );
-        let full_area_response = ui.allocate_rect(full_area_rect, Sense::click_and_drag());
+        self.handle_image_interaction(ui);
+
+        // Show image
+        self.paint_image_tiles(ui);
+    }
+
+    pub fn handle_image_interaction(&mut self, ui: &mut Ui) {
+        // Creating full pane area to handle dragging
+        let full_area_response = ui.allocate_rect(self.app_state.viewport, Sense::click_and_drag());
 
         // Render Context Menu (only visible after right click)
         full_area_response.context_menu(|ui| self.render_context_menu(ui));
 
+        // Handle dragging
+        if full_area_response.dragged() && self.app_state.dragging {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+
+            let delta = full_area_response.drag_delta();
+
+            self.image_state.offset += delta / self.image_state.zoom_factor;
+            self.image_state.offset.x = self.image_state.offset.x.clamp(-500.0, 500.0);
+            self.image_state.offset.y = self.image_state.offset.y.clamp(-500.0, 500.0);
+        }
+    }
+
+    // Largest downscaled level that still has at least one texel per screen pixel
+    fn pyramid_level(&self) -> usize {
+        let scale = self.zoom_percent() / 100.0;
+        let Some(image_size) = self.image_state.image_size() else {
+            return 0;
+        };
+        if scale <= 0.0 {
+            return 0;
+        }
+
+        let max_level = image_size.max_elem().log2().floor();
+        (1.0 / scale).log2().floor().clamp(0.0, max_level) as usize
+    }
+
+    pub fn paint_image_tiles(&mut self, ui: &mut Ui) {
+        let Some(drawn_rect) = self.calculate_drawn_rect() else {
+            return;
+        };
+
+        let ctx = ui.ctx().clone();
+        let painter = ui.painter().clone();
+        let window_rect = self.app_state.viewport;
+
+        let max_texture_side = ctx.input(|i| i.max_texture_side) as u32;
+        let Some(level) =
+            self.image_state
+                .pyramid
+                .available_level(self.pyramid_level(), max_texture_side, &ctx)
+        else {
+            // Nothing small enough to draw yet, the downscaler repaints once it's done
+            Spinner::new().paint_at(
+                ui,
+                Rect::from_center_size(window_rect.center(), Vec2::splat(32.0)),
+            );
+            return;
+        };
+
+        let texture_name = format!("{}@{}", self.image_state.uri, level);
+        let uv_rect = self.image_state.uv_rect;
+        let channel = self.image_state.channel;
+        let texture_options = self.image_state.texture_options;
+
+        let center = drawn_rect.center();
+        let rotation =
+            Rot2::from_angle(self.image_state.rotation as f32 * std::f32::consts::PI / 2.0);
+
+        // Tiles a bit off screen keep their textures, so panning back doesn't upload them again
+        let keep_rect = window_rect.expand2(window_rect.size() * 0.5);
+
+        let tile_level = self.image_state.pyramid.level(level);
+        for index in 0..tile_level.tiles.len() {
+            let tile = &tile_level.tiles[index];
+            let (tile_rect, tile_uv) = region_to_screen(tile.region, tile.uv, drawn_rect, uv_rect);
+
+            // Skip tiles that end up outside the window after rotation
+            let rotated_bounds = Rect::from_points(
+                &[
+                    tile_rect.left_top(),
+                    tile_rect.right_top(),
+                    tile_rect.left_bottom(),
+                    tile_rect.right_bottom(),
+                ]
+                .map(|corner| center + rotation * (corner - center)),
+            );
+            if !rotated_bounds.intersects(window_rect) {
+                if !rotated_bounds.intersects(keep_rect) {
+                    tile_level.unload_tile(index);
+                }
+                continue;
+            }
+
+            let texture = tile_level.texture(index, &ctx, &texture_name, channel, texture_options);
+
+            let mut mesh = Mesh::with_texture(texture.id());
+            mesh.add_rect_with_uv(tile_rect, tile_uv, Color32::WHITE);
+            mesh.rotate(rotation, center);
+            painter.add(mesh);
+        }
+
+        self.image_state.pyramid.unload_other_levels(level);
+    }
+
+    pub fn calculate_img_rect(&self) -> Rect {
         let mut img_rect = calculate_uv_rect(
             self.app_state.window_size.to_pos2(),
             self.image_state.zoom_factor,
             self.image_state.offset,
-        );
+        )
+        .translate(self.app_state.viewport.min.to_vec2());
         let mut img_size = img_rect.size();
 
         if [1u8, 3u8].contains(&self.image_state.rotation) {
@@ -30,30 +139,45 @@ impl App {
             img_rect = Rect::from_center_size(img_rect.center(), img_size);
         }
 
-        let img = Image::from_texture(self.image_state.sized_texture.unwrap())
-            .show_loading_spinner(false)
-            .alt_text("Failed to load image =(")
-            .maintain_aspect_ratio(self.app_state.maintain_aspect_ratio)
-            .fit_to_exact_size(img_size)
-            .uv(self.image_state.uv_rect)
-            .rotate(
-                self.image_state.rotation as f32 * std::f32::consts::PI / 2.0,
-                Vec2::splat(0.5),
-            );
+        img_rect
+    }
 
-        // Handle dragging
-        if full_area_response.dragged() && self.app_state.dragging {
-            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+    // Unrotated rect the image is painted into
+    pub fn calculate_drawn_rect(&self) -> Option<Rect> {
+        let image_size = self.image_state.image_size()?;
+        let img_rect = self.calculate_img_rect();
 
-            let delta = full_area_response.drag_delta();
+        // Fit the image into img_rect, keeping the aspect ratio if requested
+        let drawn_size = if self.app_state.maintain_aspect_ratio {
+            image_size * (img_rect.size() / image_size).min_elem()
+        } else {
+            img_rect.size()
+        };
 
-            self.image_state.offset += delta / self.image_state.zoom_factor;
-            self.image_state.offset.x = self.image_state.offset.x.clamp(-500.0, 500.0);
-            self.image_state.offset.y = self.image_state.offset.y.clamp(-500.0, 500.0);
+        Some(Rect::from_center_size(img_rect.center(), drawn_size))
+    }
+
+    // Screen-space bounds of the image after rotation
+    pub fn calculate_screen_rect(&self) -> Option<Rect> {
+        let drawn_rect = self.calculate_drawn_rect()?;
+
+        match [1u8, 3u8].contains(&self.image_state.rotation) {
+            true => Some(Rect::from_center_size(
+                drawn_rect.center(),
+                Vec2::new(drawn_rect.height(), drawn_rect.width()),
+            )),
+            false => Some(drawn_rect),
         }
+    }
 
-        // Show image
-        ui.put(img_rect, img);
+    pub fn pixel_under_pointer(&self, pointer: Pos2) -> Option<(u32, u32)> {
+        screen_to_image_pixel(
+            pointer,
+            self.calculate_drawn_rect()?,
+            self.image_state.rotation,
+            self.image_state.uv_rect,
+            self.image_state.image_size()?,
+        )
     }
 
     pub fn render_context_menu(&mut self, ui: &mut Ui) {
@@ -65,6 +189,18 @@ impl App {
             self.open_image();
         }
 
+        ui.menu_button(
+            format!("{} {}", icons::ICON_HISTORY, "Recent files"),
+            |ui| {
+                self.render_recent_files(ui);
+                ui.separator();
+                if ui.button("Clear recent files").clicked() {
+                    ui.close();
+                    self.app_state.session.recent_files.clear();
+                }
+            },
+        );
+
         let copy_uri_button = ui.button(format!(
             "{} {}",
             icons::ICON_FILE_COPY,
@@ -89,8 +225,138 @@ impl App {
                 .notify(String::from("Path was copied to clipboard"));
         }
 
+        let rename_button = ui.button(format!("{} {}", icons::ICON_EDIT, "Rename [F2]"));
+        if rename_button.clicked() {
+            ui.close();
+            self.app_state.rename_buffer = Some(self.image_state.info.name.clone());
+        }
+
+        let trash_button = ui.button(format!(
+            "{} {}",
+            icons::ICON_DELETE,
+            "Move to trash [Delete]"
+        ));
+        if trash_button.clicked() {
+            ui.close();
+            self.trash_image();
+        }
+
+        ui.menu_button(format!("{} {}", icons::ICON_CAMERA, "RAW decode"), |ui| {
+            for raw_decode in RawDecode::ALL {
+                let selected = self.image_state.raw_decode == raw_decode;
+                if ui
+                    .selectable_label(selected, raw_decode.to_string())
+                    .clicked()
+                {
+                    self.set_raw_decode(raw_decode);
+                }
+            }
+        });
+
+        let development_button =
+            ui.button(format!("{} {}", icons::ICON_TUNE, "RAW development [E]"));
+        if development_button.clicked() {
+            ui.close();
+            self.toggle_raw_development();
+        }
+
+        ui.menu_button(
+            format!("{} {}", icons::ICON_PHOTO_LIBRARY, "RAW+JPEG pairs"),
+            |ui| {
+                if ui
+                    .selectable_label(self.app_state.group_raw_pairs, "Group pairs")
+                    .clicked()
+                {
+                    self.toggle_group_raw_pairs();
+                }
+                if ui.button("Show the other member [J]").clicked() {
+                    ui.close();
+                    self.toggle_pair_member();
+                }
+            },
+        );
+
+        ui.menu_button(format!("{} {}", icons::ICON_STAR, "Rating"), |ui| {
+            self.render_rating_menu(ui);
+        });
+
+        let undo_button = ui.button(format!(
+            "{} {}",
+            icons::ICON_UNDO,
+            "Undo move/copy [Ctrl + Z]"
+        ));
+        if undo_button.clicked() {
+            ui.close();
+            self.undo_file_operation();
+        }
+
         ui.separator();
 
+        let histogram_button = ui.button(match self.app_state.show_histogram {
+            true => format!("{} {}", icons::ICON_BAR_CHART, "Hide histogram [G]"),
+            false => format!("{} {}", icons::ICON_BAR_CHART, "Show histogram [G]"),
+        });
+        if histogram_button.clicked() {
+            self.app_state.toggle_show_histogram();
+        }
+
+        ui.menu_button(format!("{} {}", icons::ICON_LAYERS, "Channel"), |ui| {
+            let channels = [
+                (Channel::All, "Alt + N"),
+                (Channel::Red, "Alt + R"),
+                (Channel::Green, "Alt + G"),
+                (Channel::Blue, "Alt + B"),
+                (Channel::Alpha, "Alt + A"),
+                (Channel::Luminance, "Alt + L"),
+            ];
+            for (channel, shortcut) in channels {
+                let selected = self.image_state.channel == channel;
+                if ui
+                    .selectable_label(selected, format!("{} [{}]", channel, shortcut))
+                    .clicked()
+                {
+                    self.image_state.set_channel(channel);
+                    self.app_state.notify(format!("Channel: {}", channel));
+                }
+            }
+        });
+
+        ui.menu_button(
+            format!("{} {}", icons::ICON_WALLPAPER, "Background [B]"),
+            |ui| {
+                for mode in BackgroundMode::ALL {
+                    let selected = self.app_state.background_mode == mode;
+                    if ui.selectable_label(selected, mode.to_string()).clicked() {
+                        self.set_background_mode(mode);
+                    }
+                }
+            },
+        );
+
+        let pixel_grid_button = ui.button(match self.app_state.show_pixel_grid {
+            true => format!("{} {}", icons::ICON_GRID_ON, "Hide pixel grid [Ctrl + G]"),
+            false => format!("{} {}", icons::ICON_GRID_ON, "Show pixel grid [Ctrl + G]"),
+        });
+        if pixel_grid_button.clicked() {
+            self.app_state.toggle_show_pixel_grid();
+        }
+
+        let inspector_button = ui.button(match self.app_state.show_pixel_inspector {
+            true => format!("{} {}", icons::ICON_COLORIZE, "Hide pixel inspector [K]"),
+            false => format!("{} {}", icons::ICON_COLORIZE, "Show pixel inspector [K]"),
+        });
+        if inspector_button.clicked() {
+            self.app_state.toggle_show_pixel_inspector();
+        }
+
+        let filmstrip_button = ui.button(match self.app_state.show_filmstrip {
+            true => format!("{} {}", icons::ICON_VIEW_CAROUSEL, "Hide filmstrip [N]"),
+            false => format!("{} {}", icons::ICON_VIEW_CAROUSEL, "Show filmstrip [N]"),
+        });
+        if filmstrip_button.clicked() {
+            self.toggle_show_filmstrip();
+        }
+
         let info_button = ui.button(match self.app_state.show_info {
             true => format!("{} {}", icons::ICON_TAG, "Hide info [I]"),
             false => format!("{} {}", icons::ICON_TAG, "Show info [I]"),
@@ -192,48 +458,153 @@ impl App {
                 .notify(String::from("Position Offset: (0.0, 0.0)"));
         }
 
-        let reset_zoom_button = ui.button(format!("{} {}", icons::ICON_UNDO, "Reset zoom [C]"));
+        let reset_zoom_button = ui.button(format!("{} {}", icons::ICON_UNDO, "Reset zoom [X]"));
         if reset_zoom_button.clicked() {
             self.image_state.reset_zoom();
-            self.app_state.notify(String::from("Zoom Factor: 1.0"));
+            self.notify_zoom();
         }
 
         ui.separator();
 
         let zoom_in_button = ui.button(format!("{} {}", icons::ICON_ZOOM_IN, "Zoom in [W]"));
         if zoom_in_button.clicked() {
-            self.image_state.zoom_factor += 0.1 * self.image_state.zoom_factor;
+            self.image_state
+                .set_custom_zoom(self.image_state.zoom_factor * 1.1);
         }
 
         let zoom_in_button = ui.button(format!("{} {}", icons::ICON_ZOOM_OUT, "Zoom out [S]"));
         if zoom_in_button.clicked() {
-            self.image_state.zoom_factor -= 0.1 * self.image_state.zoom_factor;
+            self.image_state
+                .set_custom_zoom(self.image_state.zoom_factor * 0.9);
         }
 
+        ui.menu_button(
+            format!("{} {}", icons::ICON_FIT_SCREEN, "Zoom mode [Z]"),
+            |ui| {
+                for mode in ZoomMode::SELECTABLE {
+                    let selected = self.image_state.zoom_mode == mode;
+                    if ui.selectable_label(selected, mode.to_string()).clicked() {
+                        self.set_zoom_mode(mode);
+                    }
+                }
+            },
+        );
+
+        ui.menu_button(format!("{} {}", icons::ICON_COMPARE, "Compare"), |ui| {
+            if ui.button("Open in new pane [Shift + O]").clicked() {
+                ui.close();
+                self.open_pane_dialog();
+            }
+
+            if self.compare.is_split() && ui.button("Close pane [Ctrl + W]").clicked() {
+                ui.close();
+                self.close_active_pane();
+            }
+
+            if ui
+                .selectable_label(self.compare.linked, "Link views [L]")
+                .clicked()
+            {
+                self.toggle_linked_view();
+            }
+
+            if ui
+                .button(format!("Split: {} [T]", self.compare.split))
+                .clicked()
+            {
+                self.cycle_split_direction();
+            }
+
+            ui.separator();
+
+            for mode in CompareMode::ALL {
+                let selected = self.compare.mode == mode;
+                if ui
+                    .selectable_label(selected, format!("{} [M]", mode))
+                    .clicked()
+                {
+                    self.set_compare_mode(mode);
+                }
+            }
+
+            if ui
+                .button(format!("Alignment: {} [Shift + M]", self.compare.alignment))
+                .clicked()
+            {
+                self.cycle_alignment();
+            }
+        });
+
         ui.separator();
 
+        let fullscreen_button =
+            ui.button(format!("{} {}", icons::ICON_FULLSCREEN, "Fullscreen [F]"));
+        if fullscreen_button.clicked() {
+            ui.close();
+            self.toggle_fullscreen(ui.ctx());
+        }
+
         let quit_button = ui.button(format!("{} {}", icons::ICON_CLOSE, "Quit [ESC]"));
         if quit_button.clicked() {
-            std::process::exit(0);
+            ui.ctx().send_viewport_cmd(ViewportCommand::Close);
         }
     }
 
     pub fn render_info(&mut self, ui: &mut Ui) {
+        let mut info_lines = vec![
+            format!("File Name: {}", self.image_state.info.name),
+            format!("File Path: {}", self.image_state.info.path.display()),
+            format!(
+                "File Size: {}",
+                convert_size(self.image_state.info.size as f64)
+            ),
+            format!("Image Format: {}", self.image_state.info.format),
+            format!(
+                "Image Resolution: {}x{}",
+                self.image_state.info.resolution.unwrap().0,
+                self.image_state.info.resolution.unwrap().1
+            ),
+        ];
+        info_lines.push(format!(
+            "Zoom: {:.0}% ({})",
+            self.zoom_percent(),
+            self.image_state.zoom_mode
+        ));
+        if let Some(status) = self.raw_status() {
+            info_lines.push(format!("RAW: {}", status));
+            if self.image_state.raw_development != RawDevelopment::default() {
+                info_lines.push(format!(
+                    "Development: {}",
+                    self.image_state.raw_development.summary()
+                ));
+            }
+        }
+        if self.image_state.channel != Channel::All {
+            info_lines.push(format!("Channel: {}", self.image_state.channel));
+        }
+        let rating = self.rating(&self.image_state.info.path.clone());
+        if rating.is_set() {
+            let mut rating_line = format!("Rating: {}", rating.stars_text());
+            if let Some(label) = rating.label {
+                rating_line.push_str(&format!(" ({})", label));
+            }
+            if rating.flag() == Flag::Picked {
+                rating_line.push_str(", Picked");
+            }
+            info_lines.push(rating_line);
+        }
+
+        // Every line takes about 20 points including the frame margins
+        let viewport = self.app_state.viewport;
         let info_rect = Rect::from_min_max(
-            Pos2::new(0.0, self.app_state.window_size.y - 100.0),
-            Pos2::new(self.app_state.window_size.x, self.app_state.window_size.y),
+            Pos2::new(
+                viewport.left(),
+                viewport.bottom() - 20.0 * info_lines.len() as f32,
+            ),
+            viewport.right_bottom(),
         );
 
-        let info_text = RichText::new(format!(
-            "File Name: {}\nFile Path: {}\nFile Size: {}\nImage Format: {}\nImage Resolution: {}x{}",
-            self.image_state.info.name,
-            self.image_state.info.path.display(),
-            convert_size(self.image_state.info.size as f64),
-            self.image_state.info.format,
-            self.image_state.info.resolution.unwrap().0,
-            self.image_state.info.resolution.unwrap().1
-        ))
-        .color(Color32::WHITE);
+        let info_text = RichText::new(info_lines.join("\n")).color(Color32::WHITE);
 
         ui.scope_builder(UiBuilder::new().max_rect(info_rect), |ui| {
             Frame::new()
@@ -250,4 +621,261 @@ impl App {
                 });
         });
     }
+
+    pub fn render_pixel_grid(&mut self, ui: &mut Ui) {
+        let (Some(screen_rect), Some(image_size)) =
+            (self.calculate_screen_rect(), self.oriented_image_size())
+        else {
+            return;
+        };
+
+        let cell_size = screen_rect.size() / image_size;
+        if cell_size.min_elem() < self.app_state.pixel_grid_min_cell_size {
+            return;
+        }
+
+        // Only lines inside the window are drawn
+        let visible_rect = screen_rect.intersect(self.app_state.viewport);
+        if !visible_rect.is_positive() {
+            return;
+        }
+
+        let painter = ui.painter_at(visible_rect);
+        let stroke = (1.0, Color32::from_black_alpha(90));
+
+        let first_column = ((visible_rect.left() - screen_rect.left()) / cell_size.x).ceil();
+        let last_column = ((visible_rect.right() - screen_rect.left()) / cell_size.x).floor();
+        for column in first_column as i64..=last_column as i64 {
+            let x = screen_rect.left() + column as f32 * cell_size.x;
+            painter.vline(x, visible_rect.y_range(), stroke);
+        }
+
+        let first_row = ((visible_rect.top() - screen_rect.top()) / cell_size.y).ceil();
+        let last_row = ((visible_rect.bottom() - screen_rect.top()) / cell_size.y).floor();
+        for row in first_row as i64..=last_row as i64 {
+            let y = screen_rect.top() + row as f32 * cell_size.y;
+            painter.hline(visible_rect.x_range(), y, stroke);
+        }
+    }
+
+    pub fn render_histogram(&mut self, ui: &mut Ui) {
+        const GRAPH_SIZE: Vec2 = Vec2::new(HISTOGRAM_BINS as f32, 100.0);
+
+        let viewport = self.app_state.viewport;
+        let histogram_rect = Rect::from_min_size(
+            Pos2::new(viewport.right() - GRAPH_SIZE.x - 30.0, viewport.top()),
+            Vec2::new(GRAPH_SIZE.x + 30.0, viewport.height()),
+        );
+
+        let log_scale = self.app_state.histogram_log_scale;
+        let background_color = self.app_state.background_color;
+        let histogram = self.image_state.histogram();
+
+        let max_bin = histogram.max_bin() as f32;
+        let scale = |count: u32| -> f32 {
+            let value = match log_scale {
+                true => (1.0 + count as f32).ln() / (1.0 + max_bin).ln(),
+                false => count as f32 / max_bin,
+            };
+            value.min(1.0) * GRAPH_SIZE.y
+        };
+
+        let clipping_text = RichText::new(format!(
+            "Shadows clipped: {:.2}%\nHighlights clipped: {:.2}%",
+            histogram.shadows_clipped_percent(),
+            histogram.highlights_clipped_percent()
+        ))
+        .color(Color32::WHITE);
+
+        let mut toggle_log_scale = false;
+
+        ui.scope_builder(UiBuilder::new().max_rect(histogram_rect), |ui| {
+            Frame::new()
+                .fill(background_color)
+                .multiply_with_opacity(0.95)
+                .corner_radius(15.0)
+                .inner_margin(10)
+                .outer_margin(5)
+                .show(ui, |ui| {
+                    let (graph_rect, _) = ui.allocate_exact_size(GRAPH_SIZE, Sense::hover());
+                    let painter = ui.painter_at(graph_rect);
+
+                    let channels = [
+                        (
+                            &histogram.red,
+                            Color32::from_rgba_unmultiplied(255, 60, 60, 110),
+                        ),
+                        (
+                            &histogram.green,
+                            Color32::from_rgba_unmultiplied(60, 255, 60, 110),
+                        ),
+                        (
+                            &histogram.blue,
+                            Color32::from_rgba_unmultiplied(60, 120, 255, 110),
+                        ),
+                    ];
+                    for (bins, color) in channels {
+                        for (i, &count) in bins.iter().enumerate() {
+                            let x = graph_rect.left() + i as f32 + 0.5;
+                            painter.line_segment(
+                                [
+                                    Pos2::new(x, graph_rect.bottom()),
+                                    Pos2::new(x, graph_rect.bottom() - scale(count)),
+                                ],
+                                (1.0, color),
+                            );
+                        }
+                    }
+
+                    let luminance_points = histogram
+                        .luminance
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &count)| {
+                            Pos2::new(
+                                graph_rect.left() + i as f32 + 0.5,
+                                graph_rect.bottom() - scale(count),
+                            )
+                        })
+                        .collect();
+                    painter.add(egui::Shape::line(
+                        luminance_points,
+                        (1.0, Color32::from_white_alpha(200)),
+                    ));
+
+                    ui.label(clipping_text);
+
+                    let mut log_scale_checkbox = log_scale;
+                    if ui
+                        .checkbox(&mut log_scale_checkbox, "Log scale [Shift + G]")
+                        .changed()
+                    {
+                        toggle_log_scale = true;
+                    }
+                });
+        });
+
+        if toggle_log_scale {
+            self.app_state.toggle_histogram_log_scale();
+        }
+    }
+
+    pub fn render_pixel_inspector(&mut self, ui: &mut Ui) {
+        let Some(pointer) = ui.ctx().pointer_hover_pos() else {
+            return;
+        };
+        let Some((x, y)) = self.pixel_under_pointer(pointer) else {
+            return;
+        };
+        let Some([r, g, b, a]) = self.image_state.pixel(x, y) else {
+            return;
+        };
+
+        if !self.app_state.dragging {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+        }
+
+        let inspector_rect = Rect::from_min_size(
+            self.app_state.viewport.min,
+            Vec2::new(self.app_state.viewport.width(), 110.0),
+        );
+
+        let inspector_text = RichText::new(format!(
+            "X: {}  Y: {}\nHex: #{:02X}{:02X}{:02X}{:02X}\nRGBA: {}, {}, {}, {}\nFloat: {:.3}, {:.3}, {:.3}, {:.3}",
+            x,
+            y,
+            r,
+            g,
+            b,
+            a,
+            r,
+            g,
+            b,
+            a,
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0
+        ))
+        .monospace()
+        .color(Color32::WHITE);
+
+        ui.scope_builder(UiBuilder::new().max_rect(inspector_rect), |ui| {
+            Frame::new()
+                .fill(self.app_state.background_color)
+                .multiply_with_opacity(0.95)
+                .corner_radius(15.0)
+                .inner_margin(10)
+                .outer_margin(5)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let (swatch_rect, _) =
+                            ui.allocate_exact_size(Vec2::splat(40.0), Sense::hover());
+                        ui.painter().rect_filled(
+                            swatch_rect,
+                            5.0,
+                            Color32::from_rgba_unmultiplied(r, g, b, a),
+                        );
+                        ui.label(inspector_text);
+                    });
+                });
+        });
+    }
+
+    pub fn render_rename_dialog(&mut self, ctx: &Context) {
+        let Some(buffer) = self.app_state.rename_buffer.as_mut() else {
+            return;
+        };
+
+        let mut confirmed = false;
+        let mut cancelled = false;
+
+        let modal = Modal::new(Id::new("rename_dialog")).show(ctx, |ui| {
+            ui.set_width(300.0);
+            ui.label(format!("{} {}", icons::ICON_EDIT, "Rename file"));
+
+            // Focus the field and select the file stem, so typing keeps the extension
+            let text_edit_id = Id::new("rename_text_edit");
+            if !ui.memory(|m| m.has_focus(text_edit_id)) {
+                let stem_len = std::path::Path::new(buffer.as_str())
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().chars().count())
+                    .unwrap_or_default();
+
+                let mut state = TextEditState::load(ctx, text_edit_id).unwrap_or_default();
+                state.cursor.set_char_range(Some(CCursorRange::two(
+                    CCursor::new(0),
+                    CCursor::new(stem_len),
+                )));
+                state.store(ctx, text_edit_id);
+
+                ui.memory_mut(|m| m.request_focus(text_edit_id));
+            }
+
+            let response = ui.add(
+                TextEdit::singleline(buffer)
+                    .id(text_edit_id)
+                    .desired_width(f32::INFINITY),
+            );
+            if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                confirmed = true;
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Rename").clicked() {
+                    confirmed = true;
+                }
+                if ui.button("Cancel").clicked() {
+                    cancelled = true;
+                }
+            });
+        });
+
+        if confirmed {
+            let new_name = self.app_state.rename_buffer.take().unwrap_or_default();
+            self.rename_image(ctx, &new_name);
+        } else if cancelled || modal.should_close() {
+            self.app_state.rename_buffer = None;
+        }
+    }
 }
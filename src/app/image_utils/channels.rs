@@ -0,0 +1,58 @@
+use super::histogram::luminance;
+
+use egui::{Color32, ColorImage};
+use image::RgbaImage;
+use rayon::prelude::*;
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Channel {
+    All,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Luminance,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Channel::All => "All (RGBA)",
+            Channel::Red => "Red",
+            Channel::Green => "Green",
+            Channel::Blue => "Blue",
+            Channel::Alpha => "Alpha",
+            Channel::Luminance => "Luminance",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+pub fn color_image_for_channel(rgba_image: &RgbaImage, channel: Channel) -> ColorImage {
+    let size = [rgba_image.width() as usize, rgba_image.height() as usize];
+
+    if channel == Channel::All {
+        return ColorImage::from_rgba_unmultiplied(size, rgba_image.as_raw());
+    }
+
+    // Isolated channels are shown as opaque greyscale
+    let pixels = rgba_image
+        .as_raw()
+        .par_chunks_exact(4)
+        .map(|p| {
+            let value = match channel {
+                Channel::Red => p[0],
+                Channel::Green => p[1],
+                Channel::Blue => p[2],
+                Channel::Alpha => p[3],
+                Channel::Luminance => luminance(p[0], p[1], p[2]),
+                Channel::All => unreachable!(),
+            };
+            Color32::from_gray(value)
+        })
+        .collect();
+
+    ColorImage::new(size, pixels)
+}
@@ -0,0 +1,112 @@
+use std::sync::{
+    Arc, Condvar, Mutex, OnceLock,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, TryRecvError},
+};
+
+// Full RAW decodes and thumbnails can take seconds and can't be stopped once they run, so
+// they get threads of their own instead of holding up the rayon pool the UI waits on.
+// The newest request goes first, requests nobody waits for anymore are dropped unstarted
+struct DecodeJob {
+    cancelled: Arc<AtomicBool>,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+struct DecodeQueue {
+    jobs: Mutex<Vec<DecodeJob>>,
+    ready: Condvar,
+}
+
+static FULL_DECODES: OnceLock<DecodeQueue> = OnceLock::new();
+// The filmstrip asks for a screenful at every step, a couple of threads keep up with it
+static THUMBNAILS: OnceLock<DecodeQueue> = OnceLock::new();
+const THUMBNAIL_WORKERS: usize = 2;
+
+fn decode_queue(queue: &'static OnceLock<DecodeQueue>, workers: usize) -> &'static DecodeQueue {
+    let mut started = false;
+    let queue = queue.get_or_init(|| {
+        started = true;
+        DecodeQueue {
+            jobs: Mutex::new(Vec::new()),
+            ready: Condvar::new(),
+        }
+    });
+
+    if started {
+        for _ in 0..workers {
+            std::thread::spawn(move || run_decode_worker(queue));
+        }
+    }
+
+    queue
+}
+
+fn run_decode_worker(queue: &DecodeQueue) {
+    loop {
+        let job = {
+            let mut jobs = queue.jobs.lock().unwrap();
+            loop {
+                jobs.retain(|job| !job.cancelled.load(Ordering::Relaxed));
+                if let Some(job) = jobs.pop() {
+                    break job;
+                }
+                jobs = queue.ready.wait(jobs).unwrap();
+            }
+        };
+
+        (job.run)();
+    }
+}
+
+// Result of a queued decode. Dropping it cancels the decode if it hasn't started yet
+pub struct PendingDecode<T> {
+    receiver: Receiver<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> PendingDecode<T> {
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.receiver.try_recv()
+    }
+}
+
+impl<T> Drop for PendingDecode<T> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn push<T: Send + 'static>(
+    queue: &DecodeQueue,
+    decode: impl FnOnce() -> T + Send + 'static,
+) -> PendingDecode<T> {
+    let (sender, receiver) = mpsc::channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let job = DecodeJob {
+        cancelled: cancelled.clone(),
+        run: Box::new(move || {
+            sender.send(decode()).ok();
+        }),
+    };
+
+    queue.jobs.lock().unwrap().push(job);
+    queue.ready.notify_one();
+
+    PendingDecode {
+        receiver,
+        cancelled,
+    }
+}
+
+pub fn queue_decode<T: Send + 'static>(
+    decode: impl FnOnce() -> T + Send + 'static,
+) -> PendingDecode<T> {
+    push(decode_queue(&FULL_DECODES, 1), decode)
+}
+
+pub fn queue_thumbnail<T: Send + 'static>(
+    decode: impl FnOnce() -> T + Send + 'static,
+) -> PendingDecode<T> {
+    push(decode_queue(&THUMBNAILS, THUMBNAIL_WORKERS), decode)
+}
@@ -0,0 +1,111 @@
+use image::RgbaImage;
+use rayon::prelude::*;
+
+pub const BINS: usize = 256;
+
+#[derive(Clone)]
+pub struct Histogram {
+    pub red: [u32; BINS],
+    pub green: [u32; BINS],
+    pub blue: [u32; BINS],
+    pub luminance: [u32; BINS],
+
+    pub pixel_count: u64,
+    pub shadows_clipped: u64,
+    pub highlights_clipped: u64,
+}
+
+impl Histogram {
+    fn empty() -> Self {
+        Self {
+            red: [0; BINS],
+            green: [0; BINS],
+            blue: [0; BINS],
+            luminance: [0; BINS],
+
+            pixel_count: 0,
+            shadows_clipped: 0,
+            highlights_clipped: 0,
+        }
+    }
+
+    pub fn compute(image: &RgbaImage) -> Self {
+        image
+            .as_raw()
+            .par_chunks_exact(4)
+            .fold(Self::empty, |mut histogram, pixel| {
+                histogram.add_pixel(pixel);
+                histogram
+            })
+            .reduce(Self::empty, Self::merge)
+    }
+
+    fn add_pixel(&mut self, pixel: &[u8]) {
+        let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+
+        // Fully transparent pixels are not part of the picture
+        if a == 0 {
+            return;
+        }
+
+        self.red[r as usize] += 1;
+        self.green[g as usize] += 1;
+        self.blue[b as usize] += 1;
+        self.luminance[luminance(r, g, b) as usize] += 1;
+
+        self.pixel_count += 1;
+        if r == 0 || g == 0 || b == 0 {
+            self.shadows_clipped += 1;
+        }
+        if r == 255 || g == 255 || b == 255 {
+            self.highlights_clipped += 1;
+        }
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for i in 0..BINS {
+            self.red[i] += other.red[i];
+            self.green[i] += other.green[i];
+            self.blue[i] += other.blue[i];
+            self.luminance[i] += other.luminance[i];
+        }
+
+        self.pixel_count += other.pixel_count;
+        self.shadows_clipped += other.shadows_clipped;
+        self.highlights_clipped += other.highlights_clipped;
+
+        self
+    }
+
+    pub fn shadows_clipped_percent(&self) -> f64 {
+        percent(self.shadows_clipped, self.pixel_count)
+    }
+
+    pub fn highlights_clipped_percent(&self) -> f64 {
+        percent(self.highlights_clipped, self.pixel_count)
+    }
+
+    // Bins 0 and 255 usually spike because of clipping, so they don't set the scale
+    pub fn max_bin(&self) -> u32 {
+        [&self.red, &self.green, &self.blue, &self.luminance]
+            .iter()
+            .flat_map(|channel| channel[1..BINS - 1].iter())
+            .copied()
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+}
+
+// Rec. 709 luma
+pub fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32).round() as u8
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+
+    part as f64 / total as f64 * 100.0
+}
@@ -3,15 +3,33 @@ use magick_rust::MagickWand;
 
 use std::error::Error;
 
-pub fn load_image_default(buf: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
-    let wand = MagickWand::new();
+fn read_with_magick(
+    buf: &[u8],
+    options: &[(&str, String)],
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let mut wand = MagickWand::new();
+    for (key, value) in options {
+        wand.set_option(key, value)?;
+    }
     wand.read_image_blob(buf)?;
 
     wand.auto_orient();
 
     let blob_png = wand.write_image_blob("PNG")?;
-    let dynamic_image = image::load_from_memory(&blob_png)?;
-    let rgba_image = dynamic_image.to_rgba8();
+    Ok(image::load_from_memory(&blob_png)?)
+}
+
+pub fn load_image_default(buf: &[u8]) -> Result<DynamicImage, Box<dyn Error>> {
+    let rgba_image = read_with_magick(buf, &[])?.to_rgba8();
 
     Ok(DynamicImage::ImageRgba8(rgba_image))
 }
+
+// Options go to ImageMagick's libraw coder (dng:*). The image keeps the 16 bits per
+// channel the RAW was developed to
+pub fn load_image_raw(
+    buf: &[u8],
+    options: &[(&str, String)],
+) -> Result<DynamicImage, Box<dyn Error>> {
+    read_with_magick(buf, options)
+}
@@ -4,7 +4,7 @@ mod jpegxl;
 mod svg;
 mod fallback;
 
-pub use default::load_image_default;
+pub use default::{load_image_default, load_image_raw};
 pub use heif::load_image_heif;
 pub use jpegxl::load_image_jpegxl;
 pub use svg::load_image_svg;
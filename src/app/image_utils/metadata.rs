@@ -0,0 +1,76 @@
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageReader};
+
+use std::{collections::BTreeMap, fs::File, io::BufReader, path::Path};
+
+pub struct ColorInfo {
+    pub color_type: String,
+    pub bits_per_channel: u16,
+    pub has_alpha: bool,
+}
+
+// Colour type of the file itself. Loaders convert to RGBA on the way, so the image crate
+// is asked first and the decoded image is only used for formats it doesn't know
+pub fn color_info(path: &Path, decoded: &DynamicImage) -> ColorInfo {
+    let original = ImageReader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .map(|decoder| decoder.original_color_type());
+
+    match original {
+        Some(color_type) => ColorInfo {
+            color_type: format!("{:?}", color_type),
+            bits_per_channel: color_type.bits_per_pixel()
+                / color_type.channel_count().max(1) as u16,
+            has_alpha: has_alpha(color_type),
+        },
+        None => {
+            let color_type = decoded.color();
+            ColorInfo {
+                color_type: format!("{:?}", color_type),
+                bits_per_channel: color_type.bits_per_pixel()
+                    / color_type.channel_count().max(1) as u16,
+                has_alpha: color_type.has_alpha(),
+            }
+        }
+    }
+}
+
+fn has_alpha(color_type: ExtendedColorType) -> bool {
+    matches!(
+        color_type,
+        ExtendedColorType::La1
+            | ExtendedColorType::La2
+            | ExtendedColorType::La4
+            | ExtendedColorType::La8
+            | ExtendedColorType::La16
+            | ExtendedColorType::Rgba1
+            | ExtendedColorType::Rgba2
+            | ExtendedColorType::Rgba4
+            | ExtendedColorType::Rgba8
+            | ExtendedColorType::Rgba16
+            | ExtendedColorType::Rgba32F
+            | ExtendedColorType::Bgra8
+            | ExtendedColorType::A8
+    )
+}
+
+// Tag name -> human readable value of the primary image. Empty if there is no EXIF
+pub fn read_exif(path: &Path) -> BTreeMap<String, String> {
+    let Ok(file) = File::open(path) else {
+        return BTreeMap::new();
+    };
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(file)) else {
+        return BTreeMap::new();
+    };
+
+    exif.fields()
+        .filter(|field| field.ifd_num == exif::In::PRIMARY)
+        .map(|field| {
+            (
+                field.tag.to_string(),
+                field.display_value().with_unit(&exif).to_string(),
+            )
+        })
+        .collect()
+}
@@ -1,25 +1,54 @@
+mod channels;
+mod decode_queue;
+mod histogram;
 mod image_loaders;
-
+pub mod metadata;
+pub mod raw;
+mod resample;
+pub mod thumbnails;
+mod tiles;
+
+pub use channels::Channel;
+pub use decode_queue::{PendingDecode, queue_thumbnail};
+pub use histogram::{BINS as HISTOGRAM_BINS, Histogram};
 pub use image_info::ImageInfo;
 pub use image_state::ImageState;
+pub use tiles::Pyramid;
 
+use crate::app::directory::is_raw_image;
 use crate::app::utils::path_as_uri;
+use crate::app::zoom::ZoomMode;
+use decode_queue::queue_decode;
 use image_loaders::*;
+use raw::{RawBase, RawDecode, RawDevelopment, develop_raw, load_raw_preview};
 
 use clipboard_rs::{Clipboard, ClipboardContext};
 use wl_clipboard_rs::copy::{
     MimeType as ClipboardMimeType, Options as ClipboardOptions, Source as ClipboardSource,
 };
 
-use egui::{ColorImage, Rect, TextureHandle, Vec2, load::SizedTexture};
-use image::{DynamicImage, GenericImageView};
+use egui::{Pos2, Rect, TextureHandle, TextureOptions, Vec2};
+use image::{DynamicImage, GenericImageView, RgbaImage};
 use rayon::prelude::*;
 
 use std::{
     error::Error,
-    path::{PathBuf, absolute},
+    path::{Path, PathBuf, absolute},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+        mpsc::TryRecvError,
+    },
 };
 
+// Source of ImageState::generation. Shared by all states, so no two pixel buffers ever get
+// the same number, whichever pane they are in
+static PIXELS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    PIXELS_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
 pub mod image_info {
     use super::*;
 
@@ -28,9 +57,12 @@ pub mod image_info {
 
         pub name: String,
         pub format: String,
+        pub loader: String,
 
         pub size: u64,
         pub resolution: Option<(u32, u32)>,
+        // Pixels are the JPEG a camera embedded in its RAW file, not the decoded RAW
+        pub embedded_preview: bool,
     }
 
     impl Default for ImageInfo {
@@ -40,9 +72,11 @@ pub mod image_info {
 
                 name: String::new(),
                 format: String::from("Unknown"),
+                loader: String::new(),
 
                 size: 0,
                 resolution: None,
+                embedded_preview: false,
             }
         }
     }
@@ -54,9 +88,11 @@ pub mod image_info {
 
                 name: self.name.clone(),
                 format: self.format.clone(),
+                loader: self.loader.clone(),
 
                 size: self.size,
                 resolution: self.resolution,
+                embedded_preview: self.embedded_preview,
             }
         }
     }
@@ -73,18 +109,124 @@ pub mod image_state {
 
         pub rotation: u8,
         pub zoom_factor: f32,
+        pub zoom_mode: ZoomMode,
 
         pub uv_rect: Rect,
         pub offset: Vec2,
 
-        pub color_image: Option<ColorImage>,
-        pub texture_handle: Option<TextureHandle>,
-        pub sized_texture: Option<SizedTexture>,
+        // Decoded (unpremultiplied) pixels, kept around after the texture upload
+        pub pixels: Arc<RgbaImage>,
+        // Changes with every new set of pixels, for caches of things made from them
+        pub generation: u64,
+        pub histogram: Option<Histogram>,
+        pub channel: Channel,
+
+        // Tiled textures at several scales, built and uploaded lazily
+        pub pyramid: Pyramid,
+        pub texture_options: TextureOptions,
+        pub backdrop_texture: Option<TextureHandle>,
+
+        pub raw_decode: RawDecode,
+        pub raw_development: RawDevelopment,
+        // Decoder output of the current RAW file, developed again when the settings change.
+        // It's as big as the pixels several times over, so it's only kept while needed
+        pub raw_base: Option<RawBase>,
+        pub keep_raw_base: bool,
+        // Full decode of a RAW file whose embedded preview is shown meanwhile
+        pub full_decode: Option<PendingDecode<FullDecode>>,
     }
 
     impl ImageState {
+        pub fn new(info: ImageInfo, pixels: RgbaImage, zoom_mode: ZoomMode) -> Self {
+            let pixels = Arc::new(pixels);
+
+            Self {
+                uri: path_as_uri(&info.path),
+                uri_to_forget: None,
+                info,
+
+                rotation: 0,
+                zoom_factor: 1.0,
+                zoom_mode,
+
+                uv_rect: Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+                offset: Vec2::ZERO,
+
+                pixels: pixels.clone(),
+                generation: next_generation(),
+                histogram: None,
+                channel: Channel::All,
+
+                pyramid: Pyramid::new(pixels),
+                texture_options: TextureOptions::LINEAR,
+                backdrop_texture: None,
+
+                raw_decode: RawDecode::Full,
+                raw_development: RawDevelopment::default(),
+                raw_base: None,
+                keep_raw_base: false,
+                full_decode: None,
+            }
+        }
+
+        // RAW files show their embedded preview right away, the rest is decoded as usual
+        fn decode(&mut self, path: &PathBuf) -> Result<(ImageInfo, RgbaImage), Box<dyn Error>> {
+            self.full_decode = None;
+            self.raw_base = None;
+
+            match load_raw_preview(path) {
+                Some((info, pixels)) => {
+                    if self.raw_decode == RawDecode::Full {
+                        self.start_full_decode(&info.path);
+                    }
+                    Ok((info, pixels))
+                }
+                None => {
+                    let (info, pixels, base) = decode_full(path, self.raw_development, None)?;
+                    self.set_raw_base(base);
+                    Ok((info, pixels))
+                }
+            }
+        }
+
+        // Adjusted images and an open development panel are likely to be developed again
+        pub fn set_raw_base(&mut self, base: Option<RawBase>) {
+            let needed = self.keep_raw_base || self.raw_development != RawDevelopment::default();
+            self.raw_base = base.filter(|_| needed);
+        }
+
+        // Also used to develop the current RAW file again with new settings
+        pub fn start_full_decode(&mut self, path: &Path) {
+            let path = path.to_path_buf();
+            let development = self.raw_development;
+            let base = self.raw_base.clone();
+            // Replacing an earlier decode cancels it if it's still queued
+            self.full_decode = Some(queue_decode(move || {
+                decode_full(&path, development, base).map_err(|e| e.to_string())
+            }));
+        }
+
+        // Swaps the preview for the decoded RAW once it's ready, keeping the view.
+        // Returns None while there's nothing new
+        pub fn poll_full_decode(&mut self) -> Option<Result<(), String>> {
+            let result = match self.full_decode.as_ref()?.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => Err(String::from("Decoder stopped")),
+            };
+            self.full_decode = None;
+
+            Some(result.map(|(info, pixels, base)| {
+                if info.path == self.info.path {
+                    self.info = info;
+                    self.set_raw_base(base);
+                    self.set_pixels(pixels);
+                }
+            }))
+        }
+
         pub fn load_new_image(&mut self, path: &PathBuf) -> Result<bool, Box<dyn Error>> {
-            let (new_img_info, new_color_image) = get_image_info(path)?;
+            let (new_img_info, new_pixels) = self.decode(path)?;
 
             if new_img_info.resolution.is_none() {
                 return Ok(false);
@@ -95,12 +237,68 @@ pub mod image_state {
             self.info = new_img_info;
             self.uri = path_as_uri(&self.info.path);
             self.zoom_factor = 1.0;
+            if self.zoom_mode == ZoomMode::Custom {
+                self.zoom_mode = ZoomMode::Fit;
+            }
             self.rotation = 0;
-            self.color_image = Some(new_color_image);
+            self.set_pixels(new_pixels);
 
             Ok(true)
         }
 
+        // Decodes the current file again, keeping zoom, offset, rotation and flips
+        pub fn reload(&mut self) -> Result<(), Box<dyn Error>> {
+            let (new_img_info, new_pixels) = self.decode(&self.info.path.clone())?;
+
+            if new_img_info.resolution.is_none() {
+                return Err("Image has no resolution".into());
+            }
+
+            self.info = new_img_info;
+            self.set_pixels(new_pixels);
+
+            Ok(())
+        }
+
+        fn set_pixels(&mut self, pixels: RgbaImage) {
+            self.pixels = Arc::new(pixels);
+            self.generation = next_generation();
+            self.pyramid.reset(self.pixels.clone());
+            self.histogram = None;
+            self.backdrop_texture = None;
+        }
+
+        // Where new_name puts the current file, nothing is renamed yet
+        pub fn rename_target(&self, new_name: &str) -> Result<PathBuf, Box<dyn Error>> {
+            let new_name = new_name.trim();
+            if new_name.is_empty() || new_name.contains(std::path::is_separator) {
+                return Err(format!("Invalid file name: {}", new_name).into());
+            }
+
+            // Keep the original extension unless the user typed a new one
+            let mut new_file_name = PathBuf::from(new_name);
+            if new_file_name.extension().is_none()
+                && let Some(ext) = self.info.path.extension()
+            {
+                new_file_name.set_extension(ext);
+            }
+
+            Ok(self.info.path.with_file_name(&new_file_name))
+        }
+
+        // The file was renamed. Texture stays loaded, only the path-derived fields change
+        pub fn set_path(&mut self, new_path: PathBuf) {
+            self.uri_to_forget = Some(self.uri.clone());
+
+            self.info.name = new_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            self.info.path = new_path;
+            self.uri = path_as_uri(&self.info.path);
+        }
+
         pub fn flip_horizontal(&mut self) {
             if self.uv_rect.min.x == 0.0 {
                 self.uv_rect.min.x = 1.0;
@@ -133,24 +331,57 @@ pub mod image_state {
 
         pub fn reset_zoom(&mut self) {
             self.zoom_factor = 1.0;
+            self.zoom_mode = ZoomMode::Fit;
         }
 
-        pub fn copy_path_to_clipboard(&mut self) {
-            let clipboard_ctx = ClipboardContext::new().unwrap();
-            clipboard_ctx
-                .set_text(self.info.path.to_string_lossy().to_string())
-                .unwrap();
+        pub fn set_custom_zoom(&mut self, zoom_factor: f32) {
+            self.zoom_factor = zoom_factor;
+            self.zoom_mode = ZoomMode::Custom;
+        }
 
-            // Clipboard-rs does not support wayland, so I have to use wl-clipboard-rs in addition to it
-            // BTW I don't know how will it work in xorg session =P
-            {
-                let opts = ClipboardOptions::new();
-                opts.copy(
-                    ClipboardSource::Bytes(self.info.path.to_string_lossy().as_bytes().into()),
-                    ClipboardMimeType::Specific(String::from("text/plain;charset=utf-8")),
-                )
-                .ok();
+        pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+            self.pixels.get_pixel_checked(x, y).map(|p| p.0)
+        }
+
+        pub fn set_channel(&mut self, channel: Channel) {
+            if self.channel == channel {
+                return;
+            }
+
+            self.channel = channel;
+            self.unload_textures();
+        }
+
+        pub fn set_texture_options(&mut self, texture_options: TextureOptions) {
+            if self.texture_options == texture_options {
+                return;
             }
+
+            // Filtering is part of the texture, so it has to be uploaded again
+            self.texture_options = texture_options;
+            self.unload_textures();
+        }
+
+        pub fn image_size(&self) -> Option<Vec2> {
+            let (width, height) = self.pixels.dimensions();
+            if width == 0 || height == 0 {
+                return None;
+            }
+
+            Some(Vec2::new(width as f32, height as f32))
+        }
+
+        pub fn unload_textures(&mut self) {
+            self.pyramid.unload_textures();
+        }
+
+        pub fn histogram(&mut self) -> &Histogram {
+            self.histogram
+                .get_or_insert_with(|| Histogram::compute(&self.pixels))
+        }
+
+        pub fn copy_path_to_clipboard(&mut self) {
+            copy_text_to_clipboard(&self.info.path.to_string_lossy());
         }
 
         pub fn copy_uri_to_clipboard(&mut self) {
@@ -171,7 +402,48 @@ pub mod image_state {
     }
 }
 
-pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, ColorImage), Box<dyn Error>> {
+pub fn copy_text_to_clipboard(text: &str) {
+    let clipboard_ctx = ClipboardContext::new().unwrap();
+    clipboard_ctx.set_text(text.to_string()).unwrap();
+
+    // Clipboard-rs does not support wayland, so I have to use wl-clipboard-rs in addition to it
+    // BTW I don't know how will it work in xorg session =P
+    {
+        let opts = ClipboardOptions::new();
+        opts.copy(
+            ClipboardSource::Bytes(text.as_bytes().into()),
+            ClipboardMimeType::Specific(String::from("text/plain;charset=utf-8")),
+        )
+        .ok();
+    }
+}
+
+pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, RgbaImage), Box<dyn Error>> {
+    let (info, image) = load_image(img_path)?;
+    Ok((info, image.into_rgba8()))
+}
+
+type FullDecode = Result<(ImageInfo, RgbaImage, Option<RawBase>), String>;
+
+// RAW files go through the development settings, whatever the decoder can't read
+// falls back to the usual loader chain
+fn decode_full(
+    path: &PathBuf,
+    development: RawDevelopment,
+    base: Option<RawBase>,
+) -> Result<(ImageInfo, RgbaImage, Option<RawBase>), Box<dyn Error>> {
+    if is_raw_image(path)
+        && let Ok((base, pixels)) = develop_raw(path, development, base)
+    {
+        return Ok((base.info.clone(), pixels, Some(base)));
+    }
+
+    let (info, pixels) = get_image_info(path)?;
+    Ok((info, pixels, None))
+}
+
+// Runs the loader chain, keeping the decoded image as the loader returned it
+pub fn load_image(img_path: &PathBuf) -> Result<(ImageInfo, DynamicImage), Box<dyn Error>> {
     let img_path = absolute(img_path)?;
     let extension = img_path
         .extension()
@@ -193,6 +465,13 @@ pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, ColorImage), Box
         "jxl" => "JPEG XL",
         "fallback" => "Unknown"
     };
+    let loader_names = common_macros::hash_map! {
+        "default" => "ImageMagick",
+        "svg" => "resvg",
+        "heif" => "libheif",
+        "jxl" => "jpegxl-rs",
+        "fallback" => "image"
+    };
 
     // Determine default loader based on extension
     if let Some(ref e) = extension
@@ -206,6 +485,7 @@ pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, ColorImage), Box
 
     let mut image: Option<DynamicImage> = None;
     let mut image_format: Option<String> = None;
+    let mut image_loader: Option<String> = None;
 
     // Try every loader until it works
     for &(ext, loader) in &loaders {
@@ -217,24 +497,22 @@ pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, ColorImage), Box
         if image.is_some() {
             image_format = match ext {
                 "default" | "fallback" => {
+                    // ImageMagick opens plenty of formats the image crate can't even guess
                     let reader = image::ImageReader::open(&img_path)?.with_guessed_format()?;
-                    let format = reader.format().unwrap();
-                    Some(format!("{:?}", format).to_uppercase())
+                    match reader.format() {
+                        Some(format) => Some(format!("{:?}", format).to_uppercase()),
+                        None => Some(formats[ext].to_string()),
+                    }
                 }
                 _ => Some(formats[ext].to_string()),
             };
+            image_loader = Some(loader_names[ext].to_string());
             break;
         }
     }
 
-    if let Some(img) = &image {
+    if let Some(img) = image {
         let image_resolution = img.dimensions();
-        let image_bytes = img.as_bytes();
-
-        let color_image = ColorImage::from_rgba_unmultiplied(
-            [image_resolution.0 as usize, image_resolution.1 as usize],
-            image_bytes,
-        );
 
         Ok((
             ImageInfo {
@@ -245,10 +523,12 @@ pub fn get_image_info(img_path: &PathBuf) -> Result<(ImageInfo, ColorImage), Box
                     .to_string_lossy()
                     .to_string(),
                 format: image_format.unwrap(),
+                loader: image_loader.unwrap(),
                 size: std::fs::metadata(&img_path)?.len(),
                 resolution: Some(image_resolution),
+                embedded_preview: false,
             },
-            color_image,
+            img,
         ))
     } else {
         Err("No loaders available".into())
@@ -0,0 +1,529 @@
+use super::ImageInfo;
+use super::image_loaders::load_image_raw;
+use crate::app::directory::is_raw_image;
+
+use image::{DynamicImage, ImageBuffer, ImageFormat, Rgb, RgbaImage, metadata::Orientation};
+use rayon::prelude::*;
+
+use std::{
+    error::Error,
+    fmt,
+    io::Cursor,
+    ops::RangeInclusive,
+    path::{Path, PathBuf, absolute},
+    sync::Arc,
+};
+
+const DAYLIGHT_TEMPERATURE: f32 = 5500.0;
+const TUNGSTEN_TEMPERATURE: f32 = 3200.0;
+pub const TEMPERATURE_RANGE: RangeInclusive<f32> = 2000.0..=12000.0;
+pub const TINT_RANGE: RangeInclusive<f32> = -100.0..=100.0;
+pub const EXPOSURE_RANGE: RangeInclusive<f32> = -3.0..=3.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RawDecode {
+    // Only the JPEG the camera embedded, good enough for culling
+    Preview,
+    // The embedded JPEG first, replaced by the decoded sensor data when it's ready
+    Full,
+}
+
+impl RawDecode {
+    pub const ALL: [RawDecode; 2] = [RawDecode::Preview, RawDecode::Full];
+}
+
+impl fmt::Display for RawDecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RawDecode::Preview => "Preview only",
+            RawDecode::Full => "Full decode",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WhiteBalance {
+    AsShot,
+    Daylight,
+    Tungsten,
+    Custom,
+}
+
+impl WhiteBalance {
+    pub const ALL: [WhiteBalance; 4] = [
+        WhiteBalance::AsShot,
+        WhiteBalance::Daylight,
+        WhiteBalance::Tungsten,
+        WhiteBalance::Custom,
+    ];
+}
+
+impl fmt::Display for WhiteBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WhiteBalance::AsShot => "As shot",
+            WhiteBalance::Daylight => "Daylight",
+            WhiteBalance::Tungsten => "Tungsten",
+            WhiteBalance::Custom => "Custom",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// libraw's user_qual values
+#[derive(Clone, Copy, PartialEq)]
+pub enum DemosaicQuality {
+    Fast,
+    Normal,
+    High,
+}
+
+impl DemosaicQuality {
+    pub const ALL: [DemosaicQuality; 3] = [
+        DemosaicQuality::Fast,
+        DemosaicQuality::Normal,
+        DemosaicQuality::High,
+    ];
+
+    fn libraw_quality(self) -> u8 {
+        match self {
+            DemosaicQuality::Fast => 0,   // Bilinear
+            DemosaicQuality::Normal => 3, // AHD
+            DemosaicQuality::High => 11,  // DHT
+        }
+    }
+}
+
+impl fmt::Display for DemosaicQuality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DemosaicQuality::Fast => "Fast (bilinear)",
+            DemosaicQuality::Normal => "Normal (AHD)",
+            DemosaicQuality::High => "High (DHT)",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Non-destructive adjustments of the full RAW decode. White balance presets and the
+// demosaic go to the decoder. ImageMagick passes nothing else on to libraw, so exposure,
+// temperature and tint are applied to a decode that kept its highlights, in linear light
+#[derive(Clone, Copy, PartialEq)]
+pub struct RawDevelopment {
+    pub white_balance: WhiteBalance,
+    // Kelvin and green-magenta shift, only used by the custom white balance
+    pub temperature: f32,
+    pub tint: f32,
+    // EV
+    pub exposure: f32,
+    // 0..1, how far below white the values above it are rolled off into range
+    pub highlight_recovery: f32,
+    pub demosaic: DemosaicQuality,
+}
+
+impl Default for RawDevelopment {
+    fn default() -> Self {
+        Self {
+            white_balance: WhiteBalance::AsShot,
+            temperature: DAYLIGHT_TEMPERATURE,
+            tint: 0.0,
+            exposure: 0.0,
+            highlight_recovery: 0.0,
+            demosaic: DemosaicQuality::Normal,
+        }
+    }
+}
+
+impl RawDevelopment {
+    // Everything but as-shot starts from libraw's daylight multipliers. Auto-bright clips
+    // the brightest percent, the same brightening is done by develop without clipping
+    fn decoder_options(&self) -> Vec<(&'static str, String)> {
+        let camera_white_balance = self.white_balance == WhiteBalance::AsShot;
+        vec![
+            ("dng:no-auto-bright", String::from("true")),
+            ("dng:use-camera-wb", camera_white_balance.to_string()),
+            (
+                "dng:interpolation-quality",
+                self.demosaic.libraw_quality().to_string(),
+            ),
+        ]
+    }
+
+    // Whether a decode made with other can be developed again instead of decoding anew
+    fn decodes_like(&self, other: &Self) -> bool {
+        self.decoder_options() == other.decoder_options()
+    }
+
+    fn channel_gains(&self) -> [f32; 3] {
+        let white_balance = match self.white_balance {
+            WhiteBalance::AsShot | WhiteBalance::Daylight => [1.0; 3],
+            WhiteBalance::Tungsten => temperature_gains(TUNGSTEN_TEMPERATURE, 0.0),
+            WhiteBalance::Custom => temperature_gains(self.temperature, self.tint),
+        };
+
+        let exposure = self.exposure.exp2();
+        white_balance.map(|gain| gain * exposure)
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match self.white_balance {
+            WhiteBalance::Custom => format!("{:.0}K, tint {:+.0}", self.temperature, self.tint),
+            white_balance => white_balance.to_string(),
+        }];
+        if self.exposure != 0.0 {
+            parts.push(format!("{:+.1} EV", self.exposure));
+        }
+        if self.highlight_recovery > 0.0 {
+            parts.push(format!(
+                "highlights {:.0}%",
+                self.highlight_recovery * 100.0
+            ));
+        }
+        parts.push(self.demosaic.to_string());
+
+        parts.join(", ")
+    }
+}
+
+// libraw's default output curve is the one of BT.709
+fn decoder_to_linear(value: f32) -> f32 {
+    match value < 0.081 {
+        true => value / 4.5,
+        false => ((value + 0.099) / 1.099).powf(1.0 / 0.45),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+// Rolls knee..peak off into knee..1, leaving the values below the knee as they are.
+// Without recovery everything above white clips
+fn recover_highlights(value: f32, knee: f32, peak: f32) -> f32 {
+    if knee >= 1.0 || peak <= 1.0 || value <= knee {
+        return value;
+    }
+
+    // Starts with the slope of the values below the knee and ends at white for the peak
+    let ratio = (peak - knee) / (1.0 - knee);
+    let t = (value - knee) / (peak - knee);
+    knee + (1.0 - knee) * ratio * t / (1.0 + (ratio - 1.0) * t)
+}
+
+// Approximate color of a black body at temperature (Tanner Helland's fit)
+fn black_body_color(temperature: f32) -> [f32; 3] {
+    let t = temperature / 100.0;
+
+    let red = match t <= 66.0 {
+        true => 255.0,
+        false => 329.69873 * (t - 60.0).powf(-0.13320476),
+    };
+    let green = match t <= 66.0 {
+        true => 99.4708 * t.ln() - 161.11957,
+        false => 288.12217 * (t - 60.0).powf(-0.075514846),
+    };
+    let blue = match t {
+        66.0.. => 255.0,
+        ..=19.0 => 0.0,
+        _ => 138.51773 * (t - 10.0).ln() - 305.0448,
+    };
+
+    [red, green, blue].map(|channel| (channel / 255.0).clamp(0.05, 1.0))
+}
+
+// Multipliers that neutralize light of temperature in a daylight balanced image.
+// Positive tint goes towards magenta like in Lightroom
+fn temperature_gains(temperature: f32, tint: f32) -> [f32; 3] {
+    let daylight = black_body_color(DAYLIGHT_TEMPERATURE);
+    let light = black_body_color(temperature);
+
+    let mut gains = [0, 1, 2].map(|channel| daylight[channel] / light[channel]);
+    let green = gains[1];
+    gains = gains.map(|gain| gain / green);
+    gains[1] /= 1.0 + tint / 200.0;
+
+    gains
+}
+
+type Rgb16Image = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+// Decoder output kept around, so changing the development doesn't decode again.
+// Levels are linear, 1.0 being the decoder's full scale
+#[derive(Clone)]
+pub struct RawBase {
+    pub info: ImageInfo,
+    pub image: Arc<Rgb16Image>,
+    // Where libraw's auto-bright would have put white
+    pub white: f32,
+    // Brightest value of each channel
+    pub peak: [f32; 3],
+    pub development: RawDevelopment,
+}
+
+impl RawBase {
+    fn new(info: ImageInfo, image: Rgb16Image, development: RawDevelopment) -> Self {
+        let (white, peak) = measure_levels(&image);
+        Self {
+            info,
+            image: Arc::new(image),
+            white,
+            peak,
+            development,
+        }
+    }
+
+    // Brightens the decode the way auto-bright would, applies the gains and rolls off
+    // what ends up above white instead of clipping it
+    pub fn develop(&self) -> RgbaImage {
+        let development = &self.development;
+        let to_linear: Vec<f32> = (0..=u16::MAX)
+            .map(|value| decoder_to_linear(value as f32 / u16::MAX as f32))
+            .collect();
+
+        let gains = development.channel_gains().map(|gain| gain / self.white);
+        let peak = (0..3)
+            .map(|channel| self.peak[channel] * gains[channel])
+            .fold(1.0, f32::max);
+        let knee = 1.0 - 0.5 * development.highlight_recovery;
+
+        let mut output = RgbaImage::new(self.image.width(), self.image.height());
+        output
+            .par_chunks_mut(4)
+            .zip(self.image.par_chunks(3))
+            .for_each(|(output, pixel)| {
+                for channel in 0..3 {
+                    let linear = to_linear[pixel[channel] as usize] * gains[channel];
+                    let recovered = recover_highlights(linear, knee, peak);
+                    output[channel] = (linear_to_srgb(recovered) * 255.0).round() as u8;
+                }
+                output[3] = u8::MAX;
+            });
+
+        output
+    }
+}
+
+// libraw's auto-bright lets this share of the pixels clip
+const AUTO_BRIGHT_CLIPPED: f64 = 0.01;
+const HISTOGRAM_LEVELS: usize = 4096;
+
+// White the way auto-bright finds it, the brightest level of the channel where the
+// clipped share is reached first, and the brightest level of each channel
+fn measure_levels(image: &Rgb16Image) -> (f32, [f32; 3]) {
+    let shift = u16::BITS - HISTOGRAM_LEVELS.ilog2();
+    let histograms = image
+        .par_chunks(3)
+        .fold(
+            || vec![[0u64; 3]; HISTOGRAM_LEVELS],
+            |mut histograms, pixel| {
+                for channel in 0..3 {
+                    histograms[(pixel[channel] >> shift) as usize][channel] += 1;
+                }
+                histograms
+            },
+        )
+        .reduce(
+            || vec![[0u64; 3]; HISTOGRAM_LEVELS],
+            |mut total, histograms| {
+                for (total, counts) in total.iter_mut().zip(histograms) {
+                    for channel in 0..3 {
+                        total[channel] += counts[channel];
+                    }
+                }
+                total
+            },
+        );
+
+    // Upper edge of a histogram level
+    let level_to_linear =
+        |level: usize| decoder_to_linear((level + 1) as f32 / HISTOGRAM_LEVELS as f32);
+    let clipped = (image.width() as f64 * image.height() as f64 * AUTO_BRIGHT_CLIPPED) as u64;
+
+    let mut white = 0.0f32;
+    let mut peak = [0.0f32; 3];
+    for channel in 0..3 {
+        let mut total = 0;
+        let mut white_level = 0;
+        for level in (0..HISTOGRAM_LEVELS).rev() {
+            let count = histograms[level][channel];
+            if count > 0 && peak[channel] == 0.0 {
+                peak[channel] = level_to_linear(level);
+            }
+            total += count;
+            if total > clipped {
+                white_level = level;
+                break;
+            }
+        }
+        white = white.max(level_to_linear(white_level));
+    }
+
+    // A black frame would otherwise be brightened without end
+    (white.max(1.0 / 256.0), peak)
+}
+
+fn raw_info(path: PathBuf, size: u64, resolution: (u32, u32), loader: &str) -> ImageInfo {
+    ImageInfo {
+        name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        format: path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_uppercase(),
+        loader: loader.to_string(),
+        size,
+        resolution: Some(resolution),
+        embedded_preview: false,
+        path,
+    }
+}
+
+// Decodes the file unless base was decoded with the same decoder options, then develops it
+pub fn develop_raw(
+    path: &Path,
+    development: RawDevelopment,
+    base: Option<RawBase>,
+) -> Result<(RawBase, RgbaImage), Box<dyn Error>> {
+    let base = match base.filter(|base| base.development.decodes_like(&development)) {
+        Some(base) => RawBase {
+            development,
+            ..base
+        },
+        None => {
+            let path = absolute(path)?;
+            let buf = std::fs::read(&path)?;
+            let image = load_image_raw(&buf, &development.decoder_options())?.into_rgb16();
+            let info = raw_info(path, buf.len() as u64, image.dimensions(), "ImageMagick");
+            RawBase::new(info, image, development)
+        }
+    };
+
+    let pixels = base.develop();
+    Ok((base, pixels))
+}
+
+// End of the JPEG starting at start and its pixel count. Lossless JPEG, which
+// CR2 and DNG use for the sensor data itself, is skipped: the image crate can't decode it
+fn scan_jpeg(buf: &[u8], start: usize) -> Option<(usize, u64)> {
+    let read_u16 = |at: usize| -> Option<usize> {
+        Some(u16::from_be_bytes([*buf.get(at)?, *buf.get(at + 1)?]) as usize)
+    };
+
+    let mut pixels = None;
+    let mut position = start + 2;
+    let mut in_scan = false;
+
+    while position + 1 < buf.len() {
+        if buf[position] != 0xFF {
+            // Entropy coded data between the markers
+            match in_scan {
+                true => position += 1,
+                false => return None,
+            }
+            continue;
+        }
+
+        let marker = buf[position + 1];
+        match marker {
+            0xD9 if in_scan => return Some((position + 2, pixels?)),
+            0xD9 => return None,
+            // Padding, stuffed zero bytes and restart markers have no length
+            0xFF => position += 1,
+            0x00 | 0xD0..=0xD7 if in_scan => position += 2,
+            0x00 | 0xD0..=0xD7 => return None,
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return None,
+            _ => {
+                let length = read_u16(position + 2)?;
+                if length < 2 {
+                    return None;
+                }
+
+                if matches!(marker, 0xC0..=0xC2) {
+                    let height = read_u16(position + 5)? as u64;
+                    let width = read_u16(position + 7)? as u64;
+                    pixels = Some(width * height);
+                }
+                in_scan |= marker == 0xDA;
+                position += 2 + length;
+            }
+        }
+    }
+
+    None
+}
+
+// RAW files carry one or more JPEG renditions (thumbnail, preview, sometimes full size)
+pub fn largest_embedded_jpeg(buf: &[u8]) -> Option<&[u8]> {
+    let mut largest: Option<(usize, usize, u64)> = None;
+    let mut position = 0;
+
+    while position + 3 <= buf.len() {
+        if buf[position..position + 3] != [0xFF, 0xD8, 0xFF] {
+            position += 1;
+            continue;
+        }
+
+        match scan_jpeg(buf, position) {
+            Some((end, pixels)) => {
+                if largest.is_none_or(|(_, _, largest_pixels)| pixels > largest_pixels) {
+                    largest = Some((position, end, pixels));
+                }
+                // Thumbnails in the EXIF of this JPEG are smaller anyway
+                position = end;
+            }
+            None => position += 1,
+        }
+    }
+
+    largest.map(|(start, end, _)| &buf[start..end])
+}
+
+// Orientation is stored in the RAW container, the preview itself is often left unrotated
+fn orientation(raw: &[u8], preview: &[u8]) -> Option<Orientation> {
+    [raw, preview].iter().find_map(|buf| {
+        let exif = exif::Reader::new()
+            .read_from_container(&mut Cursor::new(buf))
+            .ok()?;
+        let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+        Orientation::from_exif(field.value.get_uint(0)? as u8)
+    })
+}
+
+// The embedded preview of a RAW file, None for other files and RAWs without a usable one
+pub fn load_raw_preview(path: &Path) -> Option<(ImageInfo, RgbaImage)> {
+    if !is_raw_image(path) {
+        return None;
+    }
+
+    let path: PathBuf = absolute(path).ok()?;
+    let buf = std::fs::read(&path).ok()?;
+    let preview = largest_embedded_jpeg(&buf)?;
+
+    let mut image: DynamicImage =
+        image::load_from_memory_with_format(preview, ImageFormat::Jpeg).ok()?;
+    if let Some(orientation) = orientation(&buf, preview) {
+        image.apply_orientation(orientation);
+    }
+    let pixels = image.into_rgba8();
+
+    let info = ImageInfo {
+        embedded_preview: true,
+        ..raw_info(
+            path,
+            buf.len() as u64,
+            pixels.dimensions(),
+            "Embedded preview",
+        )
+    };
+
+    Some((info, pixels))
+}
@@ -0,0 +1,121 @@
+use image::{ImageBuffer, Rgba, RgbaImage, imageops};
+use rayon::prelude::*;
+
+use std::sync::LazyLock;
+
+// sRGB -> linear light, scaled to the full u16 range
+static SRGB_TO_LINEAR: LazyLock<[u16; 256]> = LazyLock::new(|| {
+    let mut lut = [0u16; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        let c = i as f32 / 255.0;
+        let linear = if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        };
+        *value = (linear * 65535.0).round() as u16;
+    }
+    lut
+});
+
+fn linear_to_srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let c = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
+
+// Linear, alpha-premultiplied components of one pixel, each in 0..=65535
+fn to_linear_premultiplied(p: &[u8]) -> [u16; 4] {
+    let alpha = p[3] as u32;
+    let premultiply = |c: u8| (SRGB_TO_LINEAR[c as usize] as u32 * alpha / 255) as u16;
+
+    [
+        premultiply(p[0]),
+        premultiply(p[1]),
+        premultiply(p[2]),
+        (alpha * 257) as u16,
+    ]
+}
+
+fn from_linear_premultiplied(r: f32, g: f32, b: f32, a: f32) -> [u8; 4] {
+    if a <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+
+    [
+        linear_to_srgb(r / a),
+        linear_to_srgb(g / a),
+        linear_to_srgb(b / a),
+        (a.min(65535.0) / 257.0).round() as u8,
+    ]
+}
+
+fn half_size(pixels: &RgbaImage) -> (u32, u32) {
+    let (width, height) = pixels.dimensions();
+    ((width / 2).max(1), (height / 2).max(1))
+}
+
+// High quality 2x reduction: Lanczos3 in linear light with premultiplied alpha
+pub fn downscale_half_lanczos(pixels: &RgbaImage) -> RgbaImage {
+    let (width, height) = pixels.dimensions();
+    let (new_width, new_height) = half_size(pixels);
+
+    let linear: Vec<u16> = pixels
+        .as_raw()
+        .par_chunks_exact(4)
+        .flat_map_iter(to_linear_premultiplied)
+        .collect();
+    let linear = ImageBuffer::<Rgba<u16>, Vec<u16>>::from_raw(width, height, linear).unwrap();
+
+    let resized = imageops::resize(
+        &linear,
+        new_width,
+        new_height,
+        imageops::FilterType::Lanczos3,
+    );
+
+    let srgb: Vec<u8> = resized
+        .as_raw()
+        .par_chunks_exact(4)
+        .flat_map_iter(|p| {
+            from_linear_premultiplied(p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32)
+        })
+        .collect();
+
+    RgbaImage::from_raw(new_width, new_height, srgb).unwrap()
+}
+
+// Cheap 2x reduction (2x2 box in linear light) for images too big for Lanczos buffers
+pub fn downscale_half_box(pixels: &RgbaImage) -> RgbaImage {
+    let (width, height) = pixels.dimensions();
+    let (new_width, new_height) = half_size(pixels);
+
+    let mut downscaled = RgbaImage::new(new_width, new_height);
+    downscaled
+        .par_chunks_exact_mut(new_width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let y = y as u32 * 2;
+            for x in 0..new_width {
+                let mut sum = [0f32; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let sample_x = (x * 2 + dx).min(width - 1);
+                    let sample_y = (y + dy).min(height - 1);
+                    let linear = to_linear_premultiplied(&pixels.get_pixel(sample_x, sample_y).0);
+                    for (s, l) in sum.iter_mut().zip(linear) {
+                        *s += l as f32 / 4.0;
+                    }
+                }
+
+                let start = x as usize * 4;
+                row[start..start + 4]
+                    .copy_from_slice(&from_linear_premultiplied(sum[0], sum[1], sum[2], sum[3]));
+            }
+        });
+
+    downscaled
+}
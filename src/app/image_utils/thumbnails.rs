@@ -0,0 +1,273 @@
+use crate::app::image_utils::{ImageInfo, load_image, raw::load_raw_preview};
+use crate::app::utils::path_as_uri;
+
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+use md5::{Digest, Md5};
+
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+// Directories of the freedesktop thumbnail cache and the longest side stored in them
+#[derive(Clone, Copy, PartialEq)]
+pub enum ThumbnailSize {
+    Normal,
+    Large,
+    XLarge,
+    XXLarge,
+}
+
+impl ThumbnailSize {
+    pub const ALL: [ThumbnailSize; 4] = [
+        ThumbnailSize::Normal,
+        ThumbnailSize::Large,
+        ThumbnailSize::XLarge,
+        ThumbnailSize::XXLarge,
+    ];
+
+    pub fn pixels(self) -> u32 {
+        match self {
+            ThumbnailSize::Normal => 128,
+            ThumbnailSize::Large => 256,
+            ThumbnailSize::XLarge => 512,
+            ThumbnailSize::XXLarge => 1024,
+        }
+    }
+
+    fn directory_name(self) -> &'static str {
+        match self {
+            ThumbnailSize::Normal => "normal",
+            ThumbnailSize::Large => "large",
+            ThumbnailSize::XLarge => "x-large",
+            ThumbnailSize::XXLarge => "xx-large",
+        }
+    }
+}
+
+// ~/.cache/thumbnails, shared with file managers. Entries are named after the MD5 of the
+// file URI and belong to the file only while their Thumb::MTime matches it
+pub struct ThumbnailCache {
+    root: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new() -> Option<Self> {
+        dirs::cache_dir().map(|cache_dir| Self {
+            root: cache_dir.join("thumbnails"),
+        })
+    }
+
+    fn entry_path(&self, uri: &str, size: ThumbnailSize) -> PathBuf {
+        let hash: String = Md5::digest(uri.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        self.root
+            .join(size.directory_name())
+            .join(format!("{}.png", hash))
+    }
+
+    // A bigger entry is scaled down when the wanted size isn't there
+    pub fn load(&self, path: &Path, size: ThumbnailSize) -> Option<RgbaImage> {
+        let uri = path_as_uri(path);
+        let mtime = modification_time(path).ok()?.to_string();
+
+        ThumbnailSize::ALL
+            .iter()
+            .filter(|s| s.pixels() >= size.pixels())
+            .find_map(|s| read_entry(&self.entry_path(&uri, *s), &uri, &mtime))
+            .map(|thumbnail| make_thumbnail(&DynamicImage::ImageRgba8(thumbnail), size.pixels()))
+    }
+
+    pub fn store(
+        &self,
+        thumbnail: &RgbaImage,
+        info: &ImageInfo,
+        size: ThumbnailSize,
+    ) -> Result<(), Box<dyn Error>> {
+        let entry_path = self.entry_path(&path_as_uri(&info.path), size);
+        let directory = entry_path
+            .parent()
+            .ok_or("Thumbnail cache has no directory")?;
+        create_private_dir(directory)?;
+
+        // Readers never see a half written entry
+        let temp_path = directory.join(format!(
+            ".aqiv-{}-{}",
+            std::process::id(),
+            entry_path.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        if let Err(e) = write_thumbnail(&temp_path, thumbnail, info) {
+            std::fs::remove_file(&temp_path).ok();
+            return Err(e);
+        }
+
+        #[cfg(unix)]
+        std::fs::set_permissions(
+            &temp_path,
+            std::os::unix::fs::PermissionsExt::from_mode(0o600),
+        )?;
+
+        std::fs::rename(&temp_path, &entry_path)?;
+
+        Ok(())
+    }
+
+    // Decodes the file only when the cache has nothing valid. A cache that can't be written
+    // (read-only home, full disk) still gives the thumbnail
+    pub fn get_or_create(
+        &self,
+        path: &Path,
+        size: ThumbnailSize,
+    ) -> Result<RgbaImage, Box<dyn Error>> {
+        if let Some(thumbnail) = self.load(path, size) {
+            return Ok(thumbnail);
+        }
+
+        let (info, image) = load_thumbnail_source(path)?;
+        let thumbnail = make_thumbnail(&image, size.pixels());
+        self.store(&thumbnail, &info, size).ok();
+
+        Ok(thumbnail)
+    }
+}
+
+// The embedded preview of a RAW file is plenty for a thumbnail and saves a full decode
+pub fn load_thumbnail_source(path: &Path) -> Result<(ImageInfo, DynamicImage), Box<dyn Error>> {
+    match load_raw_preview(path) {
+        Some((info, pixels)) => Ok((info, DynamicImage::ImageRgba8(pixels))),
+        None => load_image(&path.to_path_buf()),
+    }
+}
+
+// Other writers may use any of the three text chunks for the keys
+fn text_value(info: &png::Info, key: &str) -> Option<String> {
+    let latin1 = info
+        .uncompressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == key)
+        .map(|chunk| chunk.text.clone());
+    let compressed = || {
+        info.compressed_latin1_text
+            .iter()
+            .find(|chunk| chunk.keyword == key)
+            .and_then(|chunk| chunk.get_text().ok())
+    };
+    let utf8 = || {
+        info.utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == key)
+            .and_then(|chunk| chunk.get_text().ok())
+    };
+
+    latin1.or_else(compressed).or_else(utf8)
+}
+
+// Keys usually come before the image data, so stale entries are mostly turned down
+// without decoding them. Keys after it are only known once the image is read
+fn read_entry(entry_path: &Path, uri: &str, mtime: &str) -> Option<RgbaImage> {
+    let file = BufReader::new(File::open(entry_path).ok()?);
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+
+    let matches = |info: &png::Info, missing: bool| {
+        let matches = |key: &str, expected: &str| match text_value(info, key) {
+            Some(value) => value == expected,
+            None => missing,
+        };
+        matches("Thumb::URI", uri) && matches("Thumb::MTime", mtime)
+    };
+    if !matches(reader.info(), true) {
+        return None;
+    }
+
+    let mut buffer = vec![0; reader.output_buffer_size()?];
+    let frame = reader.next_frame(&mut buffer).ok()?;
+    buffer.truncate(frame.buffer_size());
+    reader.finish().ok();
+    if !matches(reader.info(), false) {
+        return None;
+    }
+
+    let (width, height) = (frame.width, frame.height);
+    let image = match frame.color_type {
+        png::ColorType::Rgba => {
+            DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, buffer)?)
+        }
+        png::ColorType::Rgb => DynamicImage::ImageRgb8(RgbImage::from_raw(width, height, buffer)?),
+        png::ColorType::GrayscaleAlpha => {
+            DynamicImage::ImageLumaA8(GrayAlphaImage::from_raw(width, height, buffer)?)
+        }
+        png::ColorType::Grayscale => {
+            DynamicImage::ImageLuma8(GrayImage::from_raw(width, height, buffer)?)
+        }
+        // Expanded to RGB by the transformations
+        png::ColorType::Indexed => return None,
+    };
+
+    Some(image.into_rgba8())
+}
+
+// The spec asks for entries and directories only the user can read
+fn create_private_dir(directory: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+
+    builder.create(directory)
+}
+
+// Longest side becomes size, images that already fit are kept as they are
+pub fn make_thumbnail(image: &DynamicImage, size: u32) -> RgbaImage {
+    match image.width().max(image.height()) <= size {
+        true => image.to_rgba8(),
+        false => image.thumbnail(size, size).into_rgba8(),
+    }
+}
+
+pub fn modification_time(path: &Path) -> io::Result<u64> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default())
+}
+
+// PNG with the Thumb::* text chunks from the freedesktop thumbnail spec
+pub fn write_thumbnail(
+    path: &Path,
+    thumbnail: &RgbaImage,
+    info: &ImageInfo,
+) -> Result<(), Box<dyn Error>> {
+    let (width, height) = info.resolution.unwrap_or_default();
+
+    let writer = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(writer, thumbnail.width(), thumbnail.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    for (key, value) in [
+        ("Thumb::URI", path_as_uri(&info.path)),
+        ("Thumb::MTime", modification_time(&info.path)?.to_string()),
+        ("Thumb::Size", info.size.to_string()),
+        ("Thumb::Image::Width", width.to_string()),
+        ("Thumb::Image::Height", height.to_string()),
+        ("Software", String::from("aqiv")),
+    ] {
+        encoder.add_text_chunk(key.to_string(), value)?;
+    }
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(thumbnail.as_raw())?;
+    writer.finish()?;
+
+    Ok(())
+}
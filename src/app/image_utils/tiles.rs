@@ -0,0 +1,287 @@
+use super::channels::{Channel, color_image_for_channel};
+use super::resample::{downscale_half_box, downscale_half_lanczos};
+
+use egui::{Context, Pos2, Rect, TextureHandle, TextureOptions};
+use image::{RgbaImage, imageops};
+
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        mpsc::{Receiver, Sender, channel},
+    },
+};
+
+// Upper bound for a single tile, even if the GPU could take bigger textures
+pub const MAX_TILE_SIZE: u32 = 4096;
+
+// Tiles carry a border of their neighbours' pixels, so linear filtering blends across
+// tile edges the same way it does inside a tile
+const TILE_GUTTER: u32 = 1;
+
+pub struct Tile {
+    // Region of the whole image covered by this tile, in 0..1 coordinates
+    pub region: Rect,
+    // Pixels uploaded for this tile, the region plus the gutter where there are neighbours
+    pub pixel_rect: [u32; 4],
+    // Part of the texture that shows the region
+    pub uv: Rect,
+    pub texture: Option<TextureHandle>,
+}
+
+// One level of the image pyramid, split into GPU-sized tiles
+pub struct TileLevel {
+    pub pixels: Arc<RgbaImage>,
+    pub tiles: Vec<Tile>,
+}
+
+impl TileLevel {
+    pub fn new(pixels: Arc<RgbaImage>, max_texture_side: u32) -> Self {
+        let (width, height) = pixels.dimensions();
+
+        // Images that fit into one texture stay in one piece
+        let tile_size = match width.max(height) <= max_texture_side {
+            true => width.max(height).max(1),
+            false => max_texture_side.min(MAX_TILE_SIZE) - 2 * TILE_GUTTER,
+        };
+
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(tile_size as usize) {
+            for x in (0..width).step_by(tile_size as usize) {
+                let tile_width = tile_size.min(width - x);
+                let tile_height = tile_size.min(height - y);
+
+                let left = TILE_GUTTER.min(x);
+                let top = TILE_GUTTER.min(y);
+                let right = TILE_GUTTER.min(width - x - tile_width);
+                let bottom = TILE_GUTTER.min(height - y - tile_height);
+                let texture_width = (left + tile_width + right) as f32;
+                let texture_height = (top + tile_height + bottom) as f32;
+
+                tiles.push(Tile {
+                    region: Rect::from_min_max(
+                        Pos2::new(x as f32 / width as f32, y as f32 / height as f32),
+                        Pos2::new(
+                            (x + tile_width) as f32 / width as f32,
+                            (y + tile_height) as f32 / height as f32,
+                        ),
+                    ),
+                    pixel_rect: [
+                        x - left,
+                        y - top,
+                        left + tile_width + right,
+                        top + tile_height + bottom,
+                    ],
+                    uv: Rect::from_min_max(
+                        Pos2::new(left as f32 / texture_width, top as f32 / texture_height),
+                        Pos2::new(
+                            (left + tile_width) as f32 / texture_width,
+                            (top + tile_height) as f32 / texture_height,
+                        ),
+                    ),
+                    texture: None,
+                });
+            }
+        }
+
+        Self { pixels, tiles }
+    }
+
+    pub fn is_tiled(&self) -> bool {
+        self.tiles.len() > 1
+    }
+
+    // Tiles are uploaded the first time they become visible
+    pub fn texture(
+        &mut self,
+        index: usize,
+        ctx: &Context,
+        name: &str,
+        channel: Channel,
+        texture_options: TextureOptions,
+    ) -> &TextureHandle {
+        let pixels = &self.pixels;
+        let tile = &mut self.tiles[index];
+
+        tile.texture.get_or_insert_with(|| {
+            let [x, y, width, height] = tile.pixel_rect;
+            let color_image = match (width, height) == pixels.dimensions() {
+                true => color_image_for_channel(pixels, channel),
+                false => {
+                    let tile_pixels =
+                        imageops::crop_imm(pixels.as_ref(), x, y, width, height).to_image();
+                    color_image_for_channel(&tile_pixels, channel)
+                }
+            };
+
+            ctx.load_texture(format!("{}#{}", name, index), color_image, texture_options)
+        })
+    }
+
+    pub fn unload_tile(&mut self, index: usize) {
+        self.tiles[index].texture = None;
+    }
+
+    pub fn unload(&mut self) {
+        for tile in &mut self.tiles {
+            tile.texture = None;
+        }
+    }
+}
+
+// (generation, level, pixels) sent back by the background downscaler
+type LevelResult = (u64, usize, Arc<RgbaImage>);
+
+// Level N is downscaled by 2^N. Level 0 is the decoded image itself, the rest
+// are filtered in the background and drawn once they arrive
+pub struct Pyramid {
+    pixels: Arc<RgbaImage>,
+    levels: Vec<Option<TileLevel>>,
+    requested: HashSet<usize>,
+
+    // Bumped for every new image, so results for the previous one are dropped
+    generation: u64,
+    sender: Sender<LevelResult>,
+    receiver: Receiver<LevelResult>,
+}
+
+impl Pyramid {
+    pub fn new(pixels: Arc<RgbaImage>) -> Self {
+        let (sender, receiver) = channel();
+
+        Self {
+            pixels,
+            levels: Vec::new(),
+            requested: HashSet::new(),
+
+            generation: 0,
+            sender,
+            receiver,
+        }
+    }
+
+    pub fn reset(&mut self, pixels: Arc<RgbaImage>) {
+        self.pixels = pixels;
+        self.levels.clear();
+        self.requested.clear();
+        self.generation += 1;
+    }
+
+    pub fn max_level(&self) -> usize {
+        let (width, height) = self.pixels.dimensions();
+        width.max(height).max(1).ilog2() as usize
+    }
+
+    fn is_ready(&self, level: usize) -> bool {
+        self.levels.get(level).is_some_and(Option::is_some)
+    }
+
+    fn receive_levels(&mut self, max_texture_side: u32) {
+        if self.levels.is_empty() {
+            self.levels.resize_with(self.max_level() + 1, || None);
+            self.levels[0] = Some(TileLevel::new(self.pixels.clone(), max_texture_side));
+        }
+
+        while let Ok((generation, level, pixels)) = self.receiver.try_recv() {
+            if generation == self.generation && level < self.levels.len() {
+                self.levels[level] = Some(TileLevel::new(pixels, max_texture_side));
+            }
+        }
+    }
+
+    // Level that should be drawn for the wanted one: the wanted level itself if it's ready,
+    // otherwise the closest level that is cheap enough to draw in the meantime
+    pub fn available_level(
+        &mut self,
+        wanted: usize,
+        max_texture_side: u32,
+        ctx: &Context,
+    ) -> Option<usize> {
+        self.receive_levels(max_texture_side);
+        let wanted = wanted.min(self.levels.len() - 1);
+
+        if self.is_ready(wanted) {
+            return Some(wanted);
+        }
+        self.request(wanted, max_texture_side, ctx);
+
+        if let Some(coarser) = (wanted + 1..self.levels.len()).find(|l| self.is_ready(*l)) {
+            return Some(coarser);
+        }
+
+        // Uploading every tile of a huge image at once is what the pyramid avoids,
+        // so those wait for their level instead of falling back to full resolution
+        (0..wanted)
+            .rev()
+            .find(|l| self.is_ready(*l))
+            .filter(|l| !self.level(*l).is_tiled())
+    }
+
+    fn request(&mut self, level: usize, max_texture_side: u32, ctx: &Context) {
+        if self.requested.contains(&level) {
+            return;
+        }
+
+        // Each level is made from the one above it, so wait for a chain that is already running
+        if (1..level).any(|l| self.requested.contains(&l) && !self.is_ready(l)) {
+            return;
+        }
+
+        let base = (0..level)
+            .rev()
+            .find(|l| self.is_ready(*l))
+            .unwrap_or_default();
+        let Some(source) = self.levels[base].as_ref().map(|l| l.pixels.clone()) else {
+            return;
+        };
+
+        self.requested.extend(base + 1..=level);
+
+        let generation = self.generation;
+        let sender = self.sender.clone();
+        let ctx = ctx.clone();
+
+        rayon::spawn(move || {
+            let mut pixels = source;
+            for next_level in base + 1..=level {
+                // Lanczos needs several full-size buffers, too much for levels that don't fit
+                // a texture. Only the first reductions of a huge image are box filtered
+                pixels = Arc::new(
+                    match pixels.width().max(pixels.height()) > max_texture_side {
+                        true => downscale_half_box(&pixels),
+                        false => downscale_half_lanczos(&pixels),
+                    },
+                );
+                if sender
+                    .send((generation, next_level, pixels.clone()))
+                    .is_err()
+                {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    pub fn level(&mut self, level: usize) -> &mut TileLevel {
+        self.levels[level]
+            .as_mut()
+            .expect("pyramid level is not ready")
+    }
+
+    pub fn unload_other_levels(&mut self, level: usize) {
+        for (index, tile_level) in self.levels.iter_mut().enumerate() {
+            if index != level
+                && let Some(tile_level) = tile_level
+            {
+                tile_level.unload();
+            }
+        }
+    }
+
+    pub fn unload_textures(&mut self) {
+        for tile_level in self.levels.iter_mut().flatten() {
+            tile_level.unload();
+        }
+    }
+}
@@ -1,20 +1,102 @@
 use crate::app::App;
+use crate::app::file_operations::{TransferMode, target_directory_slot};
+use crate::app::image_utils::Channel;
+use crate::app::rating::{ColorLabel, Flag, MAX_STARS};
 
-use egui::{Context, Key, Ui};
+use egui::{Context, Event, Key, ViewportCommand};
 
 impl App {
-    pub fn handle_input(&mut self, ui: &mut Ui, ctx: &Context) {
+    pub fn handle_input(&mut self, ctx: &Context) {
+        // Rename dialog and culling summary handle their own keys, so do the
+        // text fields of the RAW development sliders. Shift+P closes the summary again
+        if self.app_state.culling_summary.is_some() {
+            if ctx.input(|i| i.modifiers.shift && i.key_pressed(Key::P)) {
+                self.toggle_culling_summary();
+            }
+            return;
+        }
+        if self.app_state.rename_buffer.is_some() || ctx.wants_keyboard_input() {
+            return;
+        }
+
         let mut ui_scale_factor = ctx.zoom_factor();
+        let mut fullscreen = Self::is_fullscreen(ctx);
+        let was_fullscreen = fullscreen;
+        let mut close = false;
 
         ctx.input(|i| {
-            // Exit on Escape
+            // Leave fullscreen on Escape, exit otherwise
             if i.key_pressed(Key::Escape) {
-                std::process::exit(0);
+                match fullscreen {
+                    true => fullscreen = false,
+                    false => close = true,
+                }
+            }
+
+            // Toggle fullscreen on F / F11
+            if i.key_pressed(Key::F) || i.key_pressed(Key::F11) {
+                fullscreen = !fullscreen;
             }
 
-            // Open Image on O
+            // Open Image on O, open it next to the current one on Shift+O
             if i.key_pressed(Key::O) {
-                self.open_image();
+                match i.modifiers.shift {
+                    true => self.open_pane_dialog(),
+                    false => {
+                        self.open_image();
+                    }
+                }
+            }
+
+            // Close the active pane on Ctrl+W
+            if i.key_pressed(Key::W) && i.modifiers.ctrl {
+                self.close_active_pane();
+            }
+
+            // Link/unlink pane views on L
+            if i.key_pressed(Key::L) && !i.modifiers.alt {
+                self.toggle_linked_view();
+            }
+
+            // Switch between vertical and horizontal split on T
+            if i.key_pressed(Key::T) {
+                self.cycle_split_direction();
+            }
+
+            // Focus the next pane on Tab
+            if i.key_pressed(Key::Tab) {
+                self.focus_next_pane();
+            }
+
+            // Cycle compare modes on M, alignment of different resolutions on Shift+M
+            if i.key_pressed(Key::M) {
+                match i.modifiers.shift {
+                    true => self.cycle_alignment(),
+                    false => self.cycle_compare_mode(),
+                }
+            }
+
+            // Difference amplification on [ and ]
+            if i.key_pressed(Key::OpenBracket) {
+                self.scale_difference_amplification(0.5);
+            }
+            if i.key_pressed(Key::CloseBracket) {
+                self.scale_difference_amplification(2.0);
+            }
+
+            // Rename file on F2
+            if i.key_pressed(Key::F2) {
+                self.app_state.rename_buffer = Some(self.image_state.info.name.clone());
+            }
+
+            // Move to trash on Delete
+            if i.key_pressed(Key::Delete) {
+                self.trash_image();
+            }
+
+            // Switch between the RAW and JPEG of a pair on J
+            if i.key_pressed(Key::J) {
+                self.toggle_pair_member();
             }
 
             // Maintain Aspect Ratio on D
@@ -31,6 +113,55 @@ impl App {
                 self.app_state.toggle_show_info();
             }
 
+            // Pixel inspector on K, copy color under the cursor on Shift+K
+            if i.key_pressed(Key::K) {
+                match i.modifiers.shift {
+                    true => self.copy_color_under_pointer(i.pointer.hover_pos()),
+                    false => self.app_state.toggle_show_pixel_inspector(),
+                }
+            }
+
+            // Histogram on G, log scale on Shift+G
+            if i.key_pressed(Key::G) && !i.modifiers.alt && !i.modifiers.ctrl {
+                match i.modifiers.shift {
+                    true => self.app_state.toggle_histogram_log_scale(),
+                    false => self.app_state.toggle_show_histogram(),
+                }
+            }
+
+            // Channel isolation on Alt + R/G/B/A/L, all channels on Alt + N
+            if i.modifiers.alt {
+                let channel_keys = [
+                    (Key::R, Channel::Red),
+                    (Key::G, Channel::Green),
+                    (Key::B, Channel::Blue),
+                    (Key::A, Channel::Alpha),
+                    (Key::L, Channel::Luminance),
+                    (Key::N, Channel::All),
+                ];
+                for (key, channel) in channel_keys {
+                    if i.key_pressed(key) {
+                        self.image_state.set_channel(channel);
+                        self.app_state.notify(format!("Channel: {}", channel));
+                    }
+                }
+            }
+
+            // Filmstrip on N
+            if i.key_pressed(Key::N) && !i.modifiers.alt {
+                self.toggle_show_filmstrip();
+            }
+
+            // Cycle background modes on B
+            if i.key_pressed(Key::B) && !i.modifiers.alt {
+                self.cycle_background_mode();
+            }
+
+            // Pixel grid on Ctrl+G
+            if i.key_pressed(Key::G) && i.modifiers.ctrl {
+                self.app_state.toggle_show_pixel_grid();
+            }
+
             // Horizontal Flip on H
             if i.key_pressed(Key::H) {
                 self.image_state.flip_horizontal();
@@ -46,7 +177,7 @@ impl App {
             }
 
             // Image rotation on R
-            if i.key_pressed(Key::R) {
+            if i.key_pressed(Key::R) && !i.modifiers.alt {
                 self.image_state.rotate_image();
                 self.app_state.notify(format!(
                     "Rotation: {} deg",
@@ -61,14 +192,38 @@ impl App {
                     .notify(String::from("Position Offset: (0.0, 0.0)"));
             }
 
+            // Pick on P, reject on Shift+X, unflag on U. Picks and rejects on Shift+P
+            if i.key_pressed(Key::P) {
+                match i.modifiers.shift {
+                    true => self.toggle_culling_summary(),
+                    false => self.set_flag(Flag::Picked),
+                }
+            }
+            if i.key_pressed(Key::X) && i.modifiers.shift {
+                self.set_flag(Flag::Rejected);
+            }
+            if i.key_pressed(Key::U) {
+                self.set_flag(Flag::Unflagged);
+            }
+
+            // Toggle the RAW development panel on E
+            if i.key_pressed(Key::E) {
+                self.toggle_raw_development();
+            }
+
             // Reset zoom on X
-            if i.key_pressed(Key::X) {
+            if i.key_pressed(Key::X) && !i.modifiers.shift {
                 self.image_state.reset_zoom();
-                self.app_state.notify(String::from("Zoom Factor: 1.0"));
+                self.notify_zoom();
+            }
+
+            // Cycle zoom modes on Z
+            if i.key_pressed(Key::Z) && !i.modifiers.ctrl {
+                self.cycle_zoom_mode();
             }
 
-            // Increase UI scale on Ctrl+Plus
-            if i.modifiers.ctrl && i.key_pressed(Key::Plus) {
+            // Increase UI scale on Ctrl+Plus, or Ctrl+= where Plus needs Shift
+            if i.modifiers.ctrl && (i.key_pressed(Key::Plus) || i.key_pressed(Key::Equals)) {
                 ui_scale_factor += 0.1;
             }
 
@@ -83,6 +238,45 @@ impl App {
                 self.next_image(-1).ok();
             }
 
+            // Undo last move/copy on Ctrl+Z
+            if i.modifiers.ctrl && i.key_pressed(Key::Z) {
+                self.undo_file_operation();
+            }
+
+            // Digits alone rate: 0..5 stars, 6..9 red/yellow/green/blue labels.
+            // Ctrl+Alt+1..5 shows only images with that many stars, Ctrl+Alt+0 shows all.
+            // Target folders on 1..0: Shift moves, Ctrl copies, Alt binds a folder
+            // Physical keys are used, because Shift changes the logical key (1 -> !)
+            for event in &i.events {
+                if let Event::Key {
+                    physical_key: Some(key),
+                    pressed: true,
+                    repeat: false,
+                    modifiers,
+                    ..
+                } = event
+                    && let Some(slot) = target_directory_slot(*key)
+                {
+                    let digit = ((slot + 1) % 10) as i8;
+                    if modifiers.ctrl && modifiers.alt {
+                        if digit <= MAX_STARS {
+                            self.set_rating_filter((digit > 0).then_some(digit));
+                        }
+                    } else if modifiers.alt {
+                        self.bind_target_directory(slot);
+                    } else if modifiers.shift {
+                        self.transfer_image(slot, TransferMode::Move);
+                    } else if modifiers.ctrl {
+                        self.transfer_image(slot, TransferMode::Copy);
+                    } else if !modifiers.any() {
+                        match digit {
+                            0..=MAX_STARS => self.set_stars(digit),
+                            _ => self.toggle_color_label(ColorLabel::ALL[(digit - 6) as usize]),
+                        }
+                    }
+                }
+            }
+
             if i.events.contains(&egui::Event::Copy) {
                 match i.modifiers.shift {
                     true => {
@@ -102,32 +296,43 @@ impl App {
             let scroll = i.raw_scroll_delta.y;
             if scroll != 0.0 {
                 let old_zoom = self.image_state.zoom_factor;
-                let new_zoom = (old_zoom * (1.0 + scroll.signum() * 0.1)).clamp(0.1, 10.0);
+                let new_zoom = self.clamp_zoom_factor(old_zoom * (1.0 + scroll.signum() * 0.1));
 
                 if let Some(mouse_pos) = i.pointer.interact_pos() {
-                    let window_center = (ui.available_size() / 2.0).to_pos2();
+                    let window_center = self.app_state.viewport.center();
                     let delta = mouse_pos - window_center;
                     self.image_state.offset += delta * (1.0 / new_zoom - 1.0 / old_zoom);
                 }
 
-                self.image_state.zoom_factor = new_zoom;
+                self.image_state.set_custom_zoom(new_zoom);
             }
 
             // Zoom in on W
-            if i.key_pressed(Key::W) && i.raw_scroll_delta.y == 0.0 {
-                self.image_state.zoom_factor += 0.1 * self.image_state.zoom_factor;
+            if i.key_pressed(Key::W) && !i.modifiers.ctrl && i.raw_scroll_delta.y == 0.0 {
+                self.image_state
+                    .set_custom_zoom(self.image_state.zoom_factor * 1.1);
             }
 
             // Zoom out on S
             if i.key_pressed(Key::S) && i.raw_scroll_delta.y == 0.0 {
-                self.image_state.zoom_factor -= 0.1 * self.image_state.zoom_factor;
+                self.image_state
+                    .set_custom_zoom(self.image_state.zoom_factor * 0.9);
             }
 
-            self.image_state.zoom_factor = self.image_state.zoom_factor.clamp(0.1, 10.0);
+            self.image_state.zoom_factor = self.clamp_zoom_factor(self.image_state.zoom_factor);
 
             self.app_state.dragging = i.pointer.primary_down();
         });
 
         ctx.set_zoom_factor(ui_scale_factor);
+
+        if fullscreen != was_fullscreen {
+            self.set_fullscreen(ctx, fullscreen);
+        }
+
+        // Closing the window instead of exiting lets the session be saved
+        if close {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        }
     }
 }
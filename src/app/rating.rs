@@ -0,0 +1,359 @@
+use crate::app::App;
+use crate::app::xmp::{SidecarNaming, get_value, read_xmp, set_value, write_xmp};
+
+use egui::Ui;
+use egui_material_icons::icons;
+
+use std::{fmt, path::Path};
+
+pub const MAX_STARS: i8 = 5;
+
+// Lightroom keeps the name in xmp:Label, darktable the index in darktable:colorlabels
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorLabel {
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorLabel {
+    pub const ALL: [ColorLabel; 5] = [
+        ColorLabel::Red,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|label| label.to_string().eq_ignore_ascii_case(name))
+    }
+
+    fn darktable_index(self) -> usize {
+        Self::ALL.iter().position(|label| *label == self).unwrap()
+    }
+}
+
+impl fmt::Display for ColorLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorLabel::Red => "Red",
+            ColorLabel::Yellow => "Yellow",
+            ColorLabel::Green => "Green",
+            ColorLabel::Blue => "Blue",
+            ColorLabel::Purple => "Purple",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Picks and rejects exclude each other, a reject is a -1 star rating
+#[derive(Clone, Copy, PartialEq)]
+pub enum Flag {
+    Unflagged,
+    Picked,
+    Rejected,
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Flag::Unflagged => "Unflagged",
+            Flag::Picked => "Picked",
+            Flag::Rejected => "Rejected",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Rating {
+    // 0..=5, -1 marks a rejected image like in Lightroom and darktable
+    pub stars: i8,
+    pub label: Option<ColorLabel>,
+    pub pick: bool,
+}
+
+impl Rating {
+    pub fn read(path: &Path) -> Self {
+        let Some(xml) = read_xmp(path) else {
+            return Self::default();
+        };
+
+        let stars = get_value(&xml, "xmp:Rating")
+            .and_then(|value| value.parse::<f32>().ok())
+            .map(|value| (value as i8).clamp(-1, MAX_STARS))
+            .unwrap_or(0);
+
+        let label = get_value(&xml, "xmp:Label")
+            .and_then(|name| ColorLabel::from_name(&name))
+            .or_else(|| {
+                let sequence = get_value(&xml, "darktable:colorlabels")?;
+                let index: usize = get_value(&sequence, "rdf:li")?.parse().ok()?;
+                ColorLabel::ALL.get(index).copied()
+            });
+
+        let pick = get_value(&xml, "aqiv:Pick").is_some_and(|value| value == "True");
+
+        Self { stars, label, pick }
+    }
+
+    fn apply(self, xml: String) -> String {
+        let label = self.label.map(|l| l.to_string()).unwrap_or_default();
+        let xml = set_value(xml, "xmp:Rating", &self.stars.to_string());
+        let mut xml = set_value(xml, "xmp:Label", &label);
+
+        // Files that were never picked are kept free of aqiv:Pick
+        if self.pick || get_value(&xml, "aqiv:Pick").is_some() {
+            let pick = if self.pick { "True" } else { "False" };
+            xml = set_value(xml, "aqiv:Pick", pick);
+        }
+
+        // Only touched when darktable already wrote it, xmp:Label is enough for everyone else
+        if get_value(&xml, "darktable:colorlabels").is_none() {
+            return xml;
+        }
+        let sequence = match self.label {
+            Some(label) => format!(
+                "<rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq>",
+                label.darktable_index()
+            ),
+            None => String::from("<rdf:Seq/>"),
+        };
+        set_value(xml, "darktable:colorlabels", &sequence)
+    }
+
+    pub fn stars_text(self) -> String {
+        match self.stars {
+            ..0 => String::from("Rejected"),
+            0 => String::from("No stars"),
+            stars => icons::ICON_STAR.repeat(stars as usize),
+        }
+    }
+
+    pub fn is_set(self) -> bool {
+        self != Self::default()
+    }
+
+    pub fn flag(self) -> Flag {
+        match (self.stars, self.pick) {
+            (..0, _) => Flag::Rejected,
+            (_, true) => Flag::Picked,
+            _ => Flag::Unflagged,
+        }
+    }
+
+    // Stars survive picking and unflagging, rejecting replaces them
+    fn with_flag(self, flag: Flag) -> Self {
+        let stars = self.stars.max(0);
+        match flag {
+            Flag::Unflagged => Self {
+                stars,
+                pick: false,
+                ..self
+            },
+            Flag::Picked => Self {
+                stars,
+                pick: true,
+                ..self
+            },
+            Flag::Rejected => Self {
+                stars: -1,
+                pick: false,
+                ..self
+            },
+        }
+    }
+}
+
+impl App {
+    // Sidecars are read once, the watcher drops cached ratings when they change
+    pub fn rating(&mut self, path: &Path) -> Rating {
+        *self
+            .app_state
+            .ratings
+            .entry(path.to_path_buf())
+            .or_insert_with(|| Rating::read(path))
+    }
+
+    pub fn passes_rating_filter(&mut self, path: &Path) -> bool {
+        match self.app_state.rating_filter {
+            Some(min_stars) => self.rating(path).stars >= min_stars,
+            None => true,
+        }
+    }
+
+    fn save_rating(&mut self, rating: Rating) -> bool {
+        let path = self.image_state.info.path.clone();
+        let result = write_xmp(
+            &path,
+            self.app_state.sidecar_naming,
+            self.app_state.embed_xmp,
+            |xml| rating.apply(xml),
+        );
+
+        match result {
+            Ok(_) => {
+                self.app_state.ratings.insert(path, rating);
+                true
+            }
+            Err(e) => {
+                self.app_state
+                    .notify(format!("Couldn't save rating: {}", e));
+                false
+            }
+        }
+    }
+
+    // Flagging is for going through a shoot, so the next image comes up right away
+    pub fn set_flag(&mut self, flag: Flag) {
+        let path = self.image_state.info.path.clone();
+        let rating = self.rating(&path).with_flag(flag);
+
+        if self.save_rating(rating) {
+            self.app_state.notify(flag.to_string());
+            if flag != Flag::Unflagged {
+                self.next_image(1).ok();
+            }
+        }
+    }
+
+    pub fn set_stars(&mut self, stars: i8) {
+        let path = self.image_state.info.path.clone();
+        let rating = Rating {
+            stars,
+            ..self.rating(&path)
+        };
+
+        if self.save_rating(rating) {
+            self.app_state
+                .notify(format!("Rating: {}", rating.stars_text()));
+        }
+    }
+
+    // Setting the label the image already has removes it
+    pub fn toggle_color_label(&mut self, label: ColorLabel) {
+        let path = self.image_state.info.path.clone();
+        let mut rating = self.rating(&path);
+        rating.label = match rating.label == Some(label) {
+            true => None,
+            false => Some(label),
+        };
+
+        if self.save_rating(rating) {
+            self.app_state.notify(match rating.label {
+                Some(label) => format!("Label: {}", label),
+                None => String::from("Label: None"),
+            });
+        }
+    }
+
+    pub fn set_rating_filter(&mut self, min_stars: Option<i8>) {
+        self.app_state.rating_filter = min_stars;
+        self.app_state.notify(match min_stars {
+            Some(stars) => format!("Showing {} stars and up", stars),
+            None => String::from("Showing all images"),
+        });
+    }
+
+    // A photo.xmp sidecar belongs to every photo.* image, photo.jpg.xmp only to photo.jpg
+    pub fn forget_rating(&mut self, path: &Path) {
+        let is_sidecar = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("xmp"));
+
+        match is_sidecar {
+            true => self.app_state.ratings.retain(|image, _| {
+                [SidecarNaming::FullName, SidecarNaming::BaseName]
+                    .iter()
+                    .all(|naming| naming.sidecar_path(image) != path)
+            }),
+            false => {
+                self.app_state.ratings.remove(path);
+            }
+        }
+    }
+
+    pub fn render_rating_menu(&mut self, ui: &mut Ui) {
+        let path = self.image_state.info.path.clone();
+        let rating = self.rating(&path);
+
+        for stars in 0..=MAX_STARS {
+            let text = format!(
+                "{} [{}]",
+                Rating {
+                    stars,
+                    ..Default::default()
+                }
+                .stars_text(),
+                stars
+            );
+            if ui.selectable_label(rating.stars == stars, text).clicked() {
+                self.set_stars(stars);
+            }
+        }
+
+        ui.separator();
+
+        // Purple has no key, like in Lightroom
+        let shortcuts = ["6", "7", "8", "9"];
+        for (index, label) in ColorLabel::ALL.into_iter().enumerate() {
+            let text = match shortcuts.get(index) {
+                Some(key) => format!("{} [{}]", label, key),
+                None => label.to_string(),
+            };
+            if ui
+                .selectable_label(rating.label == Some(label), text)
+                .clicked()
+            {
+                self.toggle_color_label(label);
+            }
+        }
+
+        ui.separator();
+
+        let flags = [
+            (Flag::Picked, "P"),
+            (Flag::Rejected, "Shift + X"),
+            (Flag::Unflagged, "U"),
+        ];
+        for (flag, shortcut) in flags {
+            let text = format!("{} [{}]", flag, shortcut);
+            if ui.selectable_label(rating.flag() == flag, text).clicked() {
+                self.set_flag(flag);
+            }
+        }
+
+        let summary_button = ui.button(format!(
+            "{} {}",
+            icons::ICON_CHECKLIST,
+            "Picks and rejects [Shift + P]"
+        ));
+        if summary_button.clicked() {
+            ui.close();
+            self.toggle_culling_summary();
+        }
+
+        ui.separator();
+
+        ui.menu_button(format!("{} {}", icons::ICON_FILTER_LIST, "Show"), |ui| {
+            let filter = self.app_state.rating_filter;
+            if ui
+                .selectable_label(filter.is_none(), "All images [Ctrl + Alt + 0]")
+                .clicked()
+            {
+                self.set_rating_filter(None);
+            }
+            for stars in 1..=MAX_STARS {
+                let text = format!("{} stars and up [Ctrl + Alt + {}]", stars, stars);
+                if ui.selectable_label(filter == Some(stars), text).clicked() {
+                    self.set_rating_filter(Some(stars));
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,207 @@
+use crate::app::App;
+use crate::app::directory::is_raw_image;
+use crate::app::image_utils::raw::{
+    DemosaicQuality, EXPOSURE_RANGE, RawDecode, RawDevelopment, TEMPERATURE_RANGE, TINT_RANGE,
+    WhiteBalance,
+};
+
+use egui::{Context, Slider, Ui, Window};
+use egui_material_icons::icons;
+
+use std::time::Duration;
+
+// Decoders don't know about egui, so pending decodes are checked on a timer
+const DECODE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl App {
+    // Inactive panes are drawn too, so their decodes are picked up as well
+    pub fn poll_full_decodes(&mut self, ctx: &Context) {
+        for index in 0..self.compare.panes.len() {
+            self.with_pane(index, |app| {
+                if app.image_state.full_decode.is_none() {
+                    return;
+                }
+
+                match app.image_state.poll_full_decode() {
+                    Some(Ok(())) => (),
+                    Some(Err(e)) => app.app_state.notify(format!(
+                        "Couldn't decode {}, showing the preview: {}",
+                        app.image_state.info.name, e
+                    )),
+                    None => ctx.request_repaint_after(DECODE_POLL_INTERVAL),
+                }
+            });
+        }
+    }
+
+    pub fn set_raw_decode(&mut self, raw_decode: RawDecode) {
+        self.image_state.raw_decode = raw_decode;
+        for pane in self.compare.panes.iter_mut().flatten() {
+            pane.raw_decode = raw_decode;
+        }
+
+        let image_state = &mut self.image_state;
+        match raw_decode {
+            RawDecode::Full => {
+                if image_state.info.embedded_preview && image_state.full_decode.is_none() {
+                    image_state.start_full_decode(&image_state.info.path.clone());
+                }
+            }
+            RawDecode::Preview => image_state.full_decode = None,
+        }
+
+        self.app_state.notify(format!("RAW: {}", raw_decode));
+    }
+
+    pub fn raw_status(&self) -> Option<&'static str> {
+        let image_state = &self.image_state;
+        match (
+            image_state.info.embedded_preview,
+            image_state.full_decode.is_some(),
+        ) {
+            (true, true) => Some("Embedded preview, decoding..."),
+            (true, false) => Some("Embedded preview"),
+            (false, _) if !is_raw_image(&image_state.info.path) => None,
+            (false, true) => Some("Full decode, developing..."),
+            (false, false) => Some("Full decode"),
+        }
+    }
+
+    pub fn toggle_raw_development(&mut self) {
+        self.set_raw_development_open(!self.app_state.show_raw_development);
+    }
+
+    // Decodes are kept for the panel, closing it lets go of the ones nothing needs
+    fn set_raw_development_open(&mut self, open: bool) {
+        self.app_state.show_raw_development = open;
+        self.app_state.raw_development = self.image_state.raw_development;
+
+        for pane in
+            std::iter::once(&mut self.image_state).chain(self.compare.panes.iter_mut().flatten())
+        {
+            pane.keep_raw_base = open;
+            let base = pane.raw_base.take();
+            pane.set_raw_base(base);
+        }
+    }
+
+    // Every pane follows the settings. Embedded previews have nothing to develop,
+    // they pick the settings up with their full decode
+    pub fn set_raw_development(&mut self, development: RawDevelopment) {
+        for index in 0..self.compare.panes.len() {
+            self.with_pane(index, |app| {
+                let image_state = &mut app.image_state;
+                if image_state.raw_development == development {
+                    return;
+                }
+                image_state.raw_development = development;
+
+                // A decode still running was started with the old settings
+                let decoded = is_raw_image(&image_state.info.path)
+                    && (!image_state.info.embedded_preview
+                        || image_state.raw_decode == RawDecode::Full);
+                if decoded || image_state.full_decode.is_some() {
+                    image_state.start_full_decode(&image_state.info.path.clone());
+                }
+            });
+        }
+    }
+
+    pub fn render_raw_development(&mut self, ctx: &Context) {
+        let mut open = self.app_state.show_raw_development;
+        let mut apply = false;
+
+        Window::new(format!("{} {}", icons::ICON_TUNE, "RAW development"))
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                apply = self.render_raw_development_controls(ui);
+            });
+
+        if open != self.app_state.show_raw_development {
+            self.set_raw_development_open(open);
+        }
+        if apply {
+            self.set_raw_development(self.app_state.raw_development);
+        }
+    }
+
+    // Returns whether the settings should be applied. Sliders apply once they are let go,
+    // every step of a drag would start another develop
+    fn render_raw_development_controls(&mut self, ui: &mut Ui) -> bool {
+        let development = &mut self.app_state.raw_development;
+        let mut apply = false;
+
+        ui.label("White balance");
+        ui.horizontal(|ui| {
+            for white_balance in WhiteBalance::ALL {
+                let selected = development.white_balance == white_balance;
+                if ui
+                    .selectable_label(selected, white_balance.to_string())
+                    .clicked()
+                {
+                    development.white_balance = white_balance;
+                    apply = true;
+                }
+            }
+        });
+
+        let custom = development.white_balance == WhiteBalance::Custom;
+        let temperature = ui.add_enabled(
+            custom,
+            Slider::new(&mut development.temperature, TEMPERATURE_RANGE)
+                .text("Temperature")
+                .suffix(" K")
+                .step_by(50.0),
+        );
+        let tint = ui.add_enabled(
+            custom,
+            Slider::new(&mut development.tint, TINT_RANGE)
+                .text("Tint")
+                .step_by(1.0),
+        );
+
+        ui.separator();
+        let exposure = ui.add(
+            Slider::new(&mut development.exposure, EXPOSURE_RANGE)
+                .text("Exposure")
+                .suffix(" EV")
+                .step_by(0.1),
+        );
+        let highlights = ui.add(
+            Slider::new(&mut development.highlight_recovery, 0.0..=1.0)
+                .text("Highlight recovery")
+                .step_by(0.05),
+        );
+
+        for slider in [temperature, tint, exposure, highlights] {
+            if slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                apply = true;
+            }
+        }
+
+        ui.separator();
+        ui.label("Demosaic");
+        ui.horizontal(|ui| {
+            for demosaic in DemosaicQuality::ALL {
+                let selected = development.demosaic == demosaic;
+                if ui
+                    .selectable_label(selected, demosaic.to_string())
+                    .clicked()
+                {
+                    development.demosaic = demosaic;
+                    apply = true;
+                }
+            }
+        });
+
+        ui.separator();
+        if ui.button("Reset").clicked() {
+            *development = RawDevelopment::default();
+            apply = true;
+        }
+
+        apply
+    }
+}
@@ -0,0 +1,157 @@
+use crate::app::App;
+use crate::app::background::BackgroundMode;
+use crate::app::utils::window_title;
+use crate::app::zoom::ZoomMode;
+
+use egui::{Context, RichText, Ui, ViewportCommand};
+use egui_material_icons::icons;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+const RECENT_FILES_COUNT: usize = 10;
+
+// Kept between runs in $XDG_STATE_HOME/aqiv/session.json
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Session {
+    pub last_file: Option<PathBuf>,
+    // Geometry is kept in window system points, the way the viewport builder takes it
+    pub window_size: Option<[f32; 2]>,
+    // Wayland doesn't tell windows where they are, so this is often missing
+    pub window_position: Option<[f32; 2]>,
+    pub zoom_mode: Option<ZoomMode>,
+    pub background_mode: Option<BackgroundMode>,
+    pub recent_files: Vec<PathBuf>,
+}
+
+impl Session {
+    fn path() -> Option<PathBuf> {
+        dirs::state_dir()
+            .or_else(dirs::data_dir)
+            .map(|dir| dir.join("aqiv").join("session.json"))
+    }
+
+    // A missing or unreadable file is an empty session
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path().ok_or("No directory to keep the session in")?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn add_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES_COUNT);
+        self.last_file = Some(path.to_path_buf());
+    }
+
+    // Files that were moved or deleted since are left out
+    pub fn existing_recent_files(&self) -> Vec<PathBuf> {
+        self.recent_files
+            .iter()
+            .filter(|path| path.exists())
+            .cloned()
+            .collect()
+    }
+}
+
+impl App {
+    // Follows the opened file and the window geometry, the rest is collected on exit
+    pub fn update_session(&mut self, ctx: &Context) {
+        let path = &self.image_state.info.path;
+        if !path.as_os_str().is_empty() && self.app_state.session.last_file.as_ref() != Some(path) {
+            self.app_state.session.add_recent_file(path);
+            ctx.send_viewport_cmd(ViewportCommand::Title(window_title(
+                &self.image_state.info.name,
+            )));
+        }
+
+        if Self::is_fullscreen(ctx) {
+            return;
+        }
+
+        // Viewport rects are measured in UI points, which the UI zoom scales
+        let zoom_factor = ctx.zoom_factor();
+        let session = &mut self.app_state.session;
+        ctx.input(|i| {
+            if let Some(rect) = i.viewport().inner_rect {
+                session.window_size = Some((rect.size() * zoom_factor).into());
+            }
+            if let Some(rect) = i.viewport().outer_rect {
+                session.window_position = Some((rect.min.to_vec2() * zoom_factor).into());
+            }
+        });
+    }
+
+    pub fn save_session(&mut self) {
+        let session = &mut self.app_state.session;
+        // Custom zoom only makes sense for the image it was made on
+        if self.image_state.zoom_mode != ZoomMode::Custom {
+            session.zoom_mode = Some(self.image_state.zoom_mode);
+        }
+        session.background_mode = Some(self.app_state.background_mode);
+
+        if let Err(e) = session.save() {
+            eprintln!("Couldn't save the session: {}", e);
+        }
+    }
+
+    pub fn render_recent_files(&mut self, ui: &mut Ui) {
+        let recent_files: Vec<PathBuf> = self
+            .app_state
+            .session
+            .existing_recent_files()
+            .into_iter()
+            .filter(|path| *path != self.image_state.info.path)
+            .collect();
+
+        if recent_files.is_empty() {
+            ui.label("No recent files");
+            return;
+        }
+
+        for path in recent_files {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let button = ui
+                .button(format!("{} {}", icons::ICON_IMAGE, name))
+                .on_hover_text(path.to_string_lossy());
+            if button.clicked() {
+                ui.close();
+                self.open_file(&path);
+            }
+        }
+    }
+
+    // Shown when no image is open instead of exiting
+    pub fn render_start_screen(&mut self, ui: &mut Ui) {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 4.0);
+
+            let open_button = ui.button(
+                RichText::new(format!("{} {}", icons::ICON_FILE_OPEN, "Open image [O]")).size(18.0),
+            );
+            if open_button.clicked() {
+                self.open_image();
+            }
+
+            ui.add_space(20.0);
+            ui.label(RichText::new("Recent files").strong());
+            self.render_recent_files(ui);
+        });
+    }
+}
@@ -1,16 +1,81 @@
-use egui::{Color32, Vec2};
+use crate::app::background::BackgroundMode;
+use crate::app::culling::CullingSummary;
+use crate::app::directory::DirectoryListing;
+use crate::app::file_operations::{FileOperation, TARGET_DIRECTORIES_COUNT};
+use crate::app::filmstrip::Filmstrip;
+use crate::app::image_utils::raw::RawDevelopment;
+use crate::app::rating::Rating;
+use crate::app::session::Session;
+use crate::app::watcher::FileWatcher;
+use crate::app::xmp::SidecarNaming;
+
+use egui::{Color32, Rect, TextureHandle, Vec2};
 use egui_notify::Toasts;
 
-use std::time::Duration;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 pub struct AppState {
+    // Area of the pane being drawn, window_size is its size
     pub window_size: Vec2,
+    pub viewport: Rect,
+    pub pixels_per_point: f32,
+    pub max_zoom_percent: f32,
+    pub nearest_filter_zoom_percent: f32,
+    pub pixel_grid_min_cell_size: f32,
     pub background_color: Color32,
+    pub background_mode: BackgroundMode,
+    pub checkerboard_size: f32,
+    pub checkerboard_colors: [Color32; 2],
+    pub checkerboard_texture: Option<TextureHandle>,
 
     pub maintain_aspect_ratio: bool,
     pub show_info: bool,
+    pub show_pixel_inspector: bool,
+    pub show_histogram: bool,
+    pub show_pixel_grid: bool,
+    pub show_filmstrip: bool,
+    pub histogram_log_scale: bool,
     pub dragging: bool,
 
+    pub rename_buffer: Option<String>,
+
+    pub target_directories: [Option<PathBuf>; TARGET_DIRECTORIES_COUNT],
+    pub file_operations: Vec<FileOperation>,
+
+    pub filmstrip: Filmstrip,
+    pub session: Session,
+
+    pub ratings: HashMap<PathBuf, Rating>,
+    // Minimal number of stars an image needs to be shown while navigating
+    pub rating_filter: Option<i8>,
+    pub culling_summary: Option<CullingSummary>,
+
+    pub group_raw_pairs: bool,
+    // Which member of a RAW/JPEG pair navigation stops on
+    pub show_raw_in_pairs: bool,
+    pub sidecar_naming: SidecarNaming,
+    pub embed_xmp: bool,
+    pub show_raw_development: bool,
+    // Values in the development panel, they reach the images once a slider is let go
+    pub raw_development: RawDevelopment,
+
+    pub cursor_hide_delay: Option<Duration>,
+    pub last_pointer_activity: Instant,
+
+    pub file_watcher: Option<FileWatcher>,
+    pub directory_listing: Option<DirectoryListing>,
+    pub auto_reload: bool,
+    pub pending_reload: Option<Instant>,
+    // When the current file was first found missing
+    pub missing_since: Option<Instant>,
+    pub reload_attempts: u32,
+    pub reload_debounce: Duration,
+    pub reload_retries: u32,
+
     pub toasts: Toasts,
     pub notification_duration: Option<Duration>,
 }
@@ -25,6 +90,26 @@ impl AppState {
         self.notify(format!("Show info: {}", self.show_info));
     }
 
+    pub fn toggle_show_pixel_inspector(&mut self) {
+        self.show_pixel_inspector = !self.show_pixel_inspector;
+        self.notify(format!("Pixel inspector: {}", self.show_pixel_inspector));
+    }
+
+    pub fn toggle_show_histogram(&mut self) {
+        self.show_histogram = !self.show_histogram;
+        self.notify(format!("Show histogram: {}", self.show_histogram));
+    }
+
+    pub fn toggle_histogram_log_scale(&mut self) {
+        self.histogram_log_scale = !self.histogram_log_scale;
+        self.notify(format!("Histogram log scale: {}", self.histogram_log_scale));
+    }
+
+    pub fn toggle_show_pixel_grid(&mut self) {
+        self.show_pixel_grid = !self.show_pixel_grid;
+        self.notify(format!("Pixel grid: {}", self.show_pixel_grid));
+    }
+
     pub fn notify(&mut self, message: String) {
         self.toasts
             .basic(message)
@@ -1,6 +1,7 @@
 use crate::app::image_utils::ImageInfo;
+use crate::app::window::WindowMode;
 
-use egui::{Pos2, Rect, Vec2};
+use egui::{Pos2, Rect, Vec2, emath::Rot2};
 
 use std::{fmt::Write, path::Path};
 
@@ -23,10 +24,69 @@ pub fn calculate_uv_rect(window_size: Pos2, zoom_factor: f32, offset: Vec2) -> R
     Rect::from_min_max(Pos2::new(x_min, y_min), Pos2::new(x_max, y_max))
 }
 
-pub fn calculate_initial_window_size(img_info: &ImageInfo) -> Vec2 {
+// Maps a screen position back to image pixel coordinates.
+// `drawn_rect` is the unrotated rect the texture is painted into, `uv_rect` holds the flips
+pub fn screen_to_image_pixel(
+    pos: Pos2,
+    drawn_rect: Rect,
+    rotation: u8,
+    uv_rect: Rect,
+    image_size: Vec2,
+) -> Option<(u32, u32)> {
+    let center = drawn_rect.center();
+    let angle = rotation as f32 * std::f32::consts::PI / 2.0;
+    let unrotated_pos = center + Rot2::from_angle(-angle) * (pos - center);
+
+    let t = (unrotated_pos - drawn_rect.min) / drawn_rect.size();
+    if !(0.0..1.0).contains(&t.x) || !(0.0..1.0).contains(&t.y) {
+        return None;
+    }
+
+    let uv = uv_rect.min.to_vec2() + (uv_rect.max - uv_rect.min) * t;
+    let x = (uv.x * image_size.x).floor().clamp(0.0, image_size.x - 1.0);
+    let y = (uv.y * image_size.y).floor().clamp(0.0, image_size.y - 1.0);
+
+    Some((x as u32, y as u32))
+}
+
+// Maps a region of the image (in 0..1 coordinates) into the unrotated drawn rect.
+// Returns the screen rect and region_uv, the part of the texture showing the region,
+// flipped the same way as `uv_rect`
+pub fn region_to_screen(
+    region: Rect,
+    region_uv: Rect,
+    drawn_rect: Rect,
+    uv_rect: Rect,
+) -> (Rect, Rect) {
+    let uv_size = uv_rect.max - uv_rect.min;
+    let to_screen = |p: Pos2| drawn_rect.min + drawn_rect.size() * ((p - uv_rect.min) / uv_size);
+
+    let screen_rect = Rect::from_two_pos(to_screen(region.min), to_screen(region.max));
+
+    let flip_x = uv_rect.min.x > uv_rect.max.x;
+    let flip_y = uv_rect.min.y > uv_rect.max.y;
+    let (min_x, max_x) = match flip_x {
+        true => (region_uv.max.x, region_uv.min.x),
+        false => (region_uv.min.x, region_uv.max.x),
+    };
+    let (min_y, max_y) = match flip_y {
+        true => (region_uv.max.y, region_uv.min.y),
+        false => (region_uv.min.y, region_uv.max.y),
+    };
+    let tile_uv = Rect::from_min_max(Pos2::new(min_x, min_y), Pos2::new(max_x, max_y));
+
+    (screen_rect, tile_uv)
+}
+
+pub fn calculate_initial_window_size(img_info: &ImageInfo, window_mode: WindowMode) -> Vec2 {
     let screen_size = screen_size::get_primary_screen_size().unwrap_or_default();
     let screen_size_vec = Vec2::new(screen_size.0 as f32, screen_size.1 as f32);
 
+    // Fullscreen covers the whole screen whatever the image size
+    if window_mode == WindowMode::Fullscreen {
+        return screen_size_vec;
+    }
+
     let img_size = img_info.resolution;
 
     if let Some((img_width, img_height)) = img_size {
@@ -71,7 +131,40 @@ pub fn convert_size(size_bytes: f64) -> String {
     buffer
 }
 
+// Escapes the same bytes as GLib's g_filename_to_uri, so the URI matches the one
+// file managers hash for their thumbnail cache
 pub fn path_as_uri(path_buf: &Path) -> String {
-    let path_str = path_buf.to_str().unwrap_or_default();
-    format!("file://{}", path_str)
+    let mut uri = String::from("file://");
+
+    for &byte in path_buf.as_os_str().as_encoded_bytes() {
+        match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'/'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b'='
+            | b':'
+            | b'@' => uri.push(byte as char),
+            _ => write!(&mut uri, "%{:02X}", byte).unwrap(),
+        }
+    }
+
+    uri
+}
+
+pub fn window_title(img_name: &str) -> String {
+    format!("Quick Image Viewer - {}", img_name)
 }
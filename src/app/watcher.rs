@@ -0,0 +1,161 @@
+use crate::app::App;
+
+use egui::Context;
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode},
+};
+
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, channel},
+    time::Instant,
+};
+
+// Watches the directory of the current image. Editors and scripts often save through
+// a temporary file and a rename, which a watch on the file itself would lose track of
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    watched_directory: Option<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new(ctx: &Context) -> notify::Result<Self> {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+
+        let watcher = notify::recommended_watcher(move |event| {
+            if sender.send(event).is_ok() {
+                ctx.request_repaint();
+            }
+        })?;
+
+        Ok(Self {
+            watcher,
+            receiver,
+            watched_directory: None,
+        })
+    }
+
+    pub fn watch_directory(&mut self, directory: &Path) {
+        if self.watched_directory.as_deref() == Some(directory) {
+            return;
+        }
+
+        if let Some(old_directory) = self.watched_directory.take() {
+            self.watcher.unwatch(&old_directory).ok();
+        }
+
+        if self
+            .watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .is_ok()
+        {
+            self.watched_directory = Some(directory.to_path_buf());
+        }
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.receiver.try_iter().filter_map(Result::ok).collect()
+    }
+}
+
+fn is_write_event(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Modify(_)
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    )
+}
+
+impl App {
+    pub fn handle_file_events(&mut self, ctx: &Context) {
+        let Some(file_watcher) = &mut self.app_state.file_watcher else {
+            return;
+        };
+
+        let path = &self.image_state.info.path;
+        if let Some(directory) = path.parent()
+            && !directory.as_os_str().is_empty()
+        {
+            file_watcher.watch_directory(directory);
+        }
+
+        let events = file_watcher.events();
+
+        // Every write pushes the reload back, so a file that is still being written is left alone
+        let rewritten = events
+            .iter()
+            .any(|event| is_write_event(&event.kind) && event.paths.contains(path));
+        if rewritten && self.app_state.auto_reload {
+            self.app_state.pending_reload = Some(Instant::now());
+            self.app_state.reload_attempts = 0;
+        }
+
+        for path in events
+            .iter()
+            .filter(|event| is_write_event(&event.kind))
+            .flat_map(|event| &event.paths)
+        {
+            self.app_state.filmstrip.forget(path);
+        }
+
+        // Sidecars can also be removed or renamed by other tools
+        for path in events.iter().flat_map(|event| &event.paths) {
+            self.forget_rating(path);
+        }
+
+        // The current file shows up after a rename, it's not news to the user
+        let current_path = self.image_state.info.path.clone();
+        let new_files = events
+            .iter()
+            .flat_map(|event| &event.paths)
+            .filter(|path| self.update_directory_listing(path) && **path != current_path)
+            .count();
+        match new_files {
+            0 => (),
+            1 => self
+                .app_state
+                .notify(String::from("New image in this directory")),
+            n => self
+                .app_state
+                .notify(format!("{} new images in this directory", n)),
+        }
+
+        self.reload_pending_image(ctx);
+    }
+
+    fn reload_pending_image(&mut self, ctx: &Context) {
+        let Some(last_change) = self.app_state.pending_reload else {
+            return;
+        };
+
+        let debounce = self.app_state.reload_debounce;
+        let elapsed = last_change.elapsed();
+        if elapsed < debounce {
+            ctx.request_repaint_after(debounce - elapsed);
+            return;
+        }
+
+        match self.image_state.reload() {
+            Ok(()) => {
+                self.app_state.pending_reload = None;
+                self.app_state
+                    .notify(format!("Reloaded {}", self.image_state.info.name));
+            }
+            // Probably caught the file half-written, give the writer some more time
+            Err(_) if self.app_state.reload_attempts < self.app_state.reload_retries => {
+                self.app_state.reload_attempts += 1;
+                self.app_state.pending_reload = Some(Instant::now());
+                ctx.request_repaint_after(debounce);
+            }
+            Err(e) => {
+                self.app_state.pending_reload = None;
+                self.app_state
+                    .notify(format!("Failed to reload image: {}", e));
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use crate::app::App;
+
+use egui::{Context, CursorIcon, Vec2, ViewportCommand};
+
+use std::time::Instant;
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+impl App {
+    pub fn is_fullscreen(ctx: &Context) -> bool {
+        ctx.input(|i| i.viewport().fullscreen.unwrap_or(false))
+    }
+
+    pub fn set_fullscreen(&mut self, ctx: &Context, fullscreen: bool) {
+        ctx.send_viewport_cmd(ViewportCommand::Fullscreen(fullscreen));
+        self.app_state.notify(format!("Fullscreen: {}", fullscreen));
+    }
+
+    pub fn toggle_fullscreen(&mut self, ctx: &Context) {
+        self.set_fullscreen(ctx, !Self::is_fullscreen(ctx));
+    }
+
+    // Hides the cursor in fullscreen once the mouse has been idle for a while
+    pub fn update_cursor_visibility(&mut self, ctx: &Context) {
+        let Some(delay) = self.app_state.cursor_hide_delay else {
+            return;
+        };
+
+        let active = ctx.input(|i| {
+            i.pointer.delta() != Vec2::ZERO
+                || i.pointer.any_down()
+                || i.raw_scroll_delta != Vec2::ZERO
+        });
+        if active {
+            self.app_state.last_pointer_activity = Instant::now();
+        }
+
+        if !Self::is_fullscreen(ctx) {
+            return;
+        }
+
+        let idle = self.app_state.last_pointer_activity.elapsed();
+        match idle >= delay {
+            true => ctx.set_cursor_icon(CursorIcon::None),
+            // Nothing else would wake the app up when the time runs out
+            false => ctx.request_repaint_after(delay - idle),
+        }
+    }
+}
@@ -0,0 +1,452 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+const XMP_NAMESPACE: &str = "http://ns.adobe.com/xap/1.0/";
+// For what no common schema has a place for, like pick flags
+const AQIV_NAMESPACE: &str = "https://aqiv.arabianq.ru/xmp/1.0/";
+const NAMESPACES: [(&str, &str); 2] = [("xmp", XMP_NAMESPACE), ("aqiv", AQIV_NAMESPACE)];
+const JPEG_XMP_SIGNATURE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+const EMPTY_XMP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="aqiv">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/">
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+
+// darktable names sidecars photo.jpg.xmp, Lightroom photo.xmp
+#[derive(Clone, Copy, PartialEq)]
+pub enum SidecarNaming {
+    FullName,
+    BaseName,
+}
+
+impl SidecarNaming {
+    pub fn sidecar_path(self, path: &Path) -> PathBuf {
+        match self {
+            SidecarNaming::FullName => {
+                let mut name = path.file_name().unwrap_or_default().to_os_string();
+                name.push(".xmp");
+                path.with_file_name(name)
+            }
+            SidecarNaming::BaseName => path.with_extension("xmp"),
+        }
+    }
+}
+
+pub fn existing_sidecars(path: &Path) -> Vec<PathBuf> {
+    [SidecarNaming::FullName, SidecarNaming::BaseName]
+        .iter()
+        .map(|naming| naming.sidecar_path(path))
+        .filter(|sidecar| sidecar.is_file())
+        .collect()
+}
+
+pub fn existing_sidecar(path: &Path) -> Option<PathBuf> {
+    existing_sidecars(path).into_iter().next()
+}
+
+// photo.xmp belongs to photo.cr2 as much as to photo.jpg. It's shared while another
+// file with that stem stays in the directory, leaving lists files going away with path
+pub fn shares_base_sidecar(path: &Path, leaving: &[PathBuf]) -> bool {
+    // Better to leave the sidecar behind than to take someone else's edits
+    let Some(entries) = path.parent().and_then(|dir| std::fs::read_dir(dir).ok()) else {
+        return true;
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|other| other.file_stem() == path.file_stem() && other.is_file())
+        .filter(|other| {
+            !other
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("xmp"))
+        })
+        .any(|other| {
+            // Siblings in the same directory, the names are enough
+            let name = other.file_name();
+            name != path.file_name() && !leaving.iter().any(|file| file.file_name() == name)
+        })
+}
+
+// Sidecars that can go wherever path goes, without a photo.xmp still in use by others
+pub fn own_sidecars(path: &Path, leaving: &[PathBuf]) -> Vec<PathBuf> {
+    let shared = shares_base_sidecar(path, leaving);
+    existing_sidecars(path)
+        .into_iter()
+        .filter(|sidecar| !shared || *sidecar != SidecarNaming::BaseName.sidecar_path(path))
+        .collect()
+}
+
+fn is_jpeg(path: &Path) -> bool {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    matches!(extension.as_deref(), Some("jpg" | "jpeg"))
+}
+
+// The sidecar wins over the file itself, that's where editors keep their changes
+pub fn read_xmp(path: &Path) -> Option<String> {
+    if let Some(sidecar) = existing_sidecar(path) {
+        return std::fs::read_to_string(sidecar).ok();
+    }
+
+    match is_jpeg(path) {
+        true => read_jpeg_xmp(path),
+        false => None,
+    }
+}
+
+// An existing sidecar is always updated, so it never shadows a newer embedded packet.
+// Embedding is only possible in JPEG, other formats get a sidecar
+pub fn write_xmp(
+    path: &Path,
+    naming: SidecarNaming,
+    embed: bool,
+    edit: impl FnOnce(String) -> String,
+) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(sidecar) = existing_sidecar(path) {
+        let xml = std::fs::read_to_string(&sidecar)?;
+        std::fs::write(&sidecar, edit(xml))?;
+        return Ok(sidecar);
+    }
+
+    if embed && is_jpeg(path) {
+        let xml = read_jpeg_xmp(path).unwrap_or_else(|| EMPTY_XMP.to_string());
+        write_jpeg_xmp(path, &edit(xml))?;
+        return Ok(path.to_path_buf());
+    }
+
+    let sidecar = naming.sidecar_path(path);
+    std::fs::write(&sidecar, edit(EMPTY_XMP.to_string()))?;
+    Ok(sidecar)
+}
+
+// Index right after name="...", name='...' or <name>, where the value starts.
+// The name has to follow whitespace or <, so xmp:Rating doesn't match xmp:RatingPercent
+fn find_value(xml: &str, name: &str) -> Option<(usize, usize)> {
+    let bytes = xml.as_bytes();
+    let mut from = 0;
+
+    while let Some(found) = xml[from..].find(name) {
+        let start = from + found;
+        let end = start + name.len();
+        from = end;
+
+        let before = start.checked_sub(1).map(|i| bytes[i]);
+        match (before, bytes.get(end), bytes.get(end + 1)) {
+            (Some(b), Some(b'='), Some(quote @ (b'"' | b'\''))) if b.is_ascii_whitespace() => {
+                let value_start = end + 2;
+                let value_end = value_start + xml[value_start..].find(*quote as char)?;
+                return Some((value_start, value_end));
+            }
+            (Some(b'<'), Some(b'>'), _) => {
+                let value_start = end + 1;
+                let value_end = value_start + xml[value_start..].find(&format!("</{}>", name))?;
+                return Some((value_start, value_end));
+            }
+            _ => (),
+        }
+    }
+
+    None
+}
+
+pub fn get_value(xml: &str, name: &str) -> Option<String> {
+    find_value(xml, name).map(|(start, end)| xml[start..end].trim().to_string())
+}
+
+// Changes the value in place, or adds it as an attribute of the first rdf:Description.
+// Everything else in the packet stays as it was
+pub fn set_value(xml: String, name: &str, value: &str) -> String {
+    if let Some((start, end)) = find_value(&xml, name) {
+        let mut xml = xml;
+        xml.replace_range(start..end, value);
+        return xml;
+    }
+
+    let Some(description) = xml.find("<rdf:Description") else {
+        return xml;
+    };
+    let insert_at = description + "<rdf:Description".len();
+
+    let mut attributes = format!(" {}=\"{}\"", name, value);
+    let prefix = name.split(':').next().unwrap_or_default();
+    if let Some((_, namespace)) = NAMESPACES.iter().find(|(known, _)| *known == prefix)
+        && !xml.contains(&format!("xmlns:{}=", prefix))
+    {
+        attributes.push_str(&format!(" xmlns:{}=\"{}\"", prefix, namespace));
+    }
+
+    let mut xml = xml;
+    xml.insert_str(insert_at, &attributes);
+    xml
+}
+
+// Marker and payload position of a segment before the image data
+struct JpegSegment {
+    marker: u8,
+    start: usize,
+    length: usize,
+}
+
+impl JpegSegment {
+    fn end(&self) -> usize {
+        self.start + self.length
+    }
+}
+
+fn jpeg_segments(reader: &mut (impl Read + Seek)) -> Result<Vec<JpegSegment>, Box<dyn Error>> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+    if header != [0xFF, 0xD8] {
+        return Err("Not a JPEG file".into());
+    }
+
+    let mut segments = Vec::new();
+    loop {
+        let mut marker = [0u8; 4];
+        reader.read_exact(&mut marker)?;
+        if marker[0] != 0xFF {
+            return Err("Broken JPEG segment".into());
+        }
+        // Start of scan, image data follows
+        if marker[1] == 0xDA {
+            return Ok(segments);
+        }
+
+        let segment = JpegSegment {
+            marker: marker[1],
+            start: reader.stream_position()? as usize,
+            length: (u16::from_be_bytes([marker[2], marker[3]]) as usize).saturating_sub(2),
+        };
+        reader.seek(SeekFrom::Start(segment.end() as u64))?;
+        segments.push(segment);
+    }
+}
+
+fn read_jpeg_xmp(path: &Path) -> Option<String> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+
+    for segment in jpeg_segments(&mut reader).ok()? {
+        if segment.marker != 0xE1 || segment.length < JPEG_XMP_SIGNATURE.len() {
+            continue;
+        }
+
+        let mut payload = vec![0u8; segment.length];
+        reader.seek(SeekFrom::Start(segment.start as u64)).ok()?;
+        reader.read_exact(&mut payload).ok()?;
+        if let Some(xml) = payload.strip_prefix(JPEG_XMP_SIGNATURE) {
+            return String::from_utf8(xml.to_vec()).ok();
+        }
+    }
+
+    None
+}
+
+// Replaces the XMP segment, or adds one after the JFIF/EXIF headers.
+// The file is written next to the original and renamed over it
+fn write_jpeg_xmp(path: &Path, xml: &str) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let segments = jpeg_segments(&mut std::io::Cursor::new(&data))?;
+
+    let payload_length = JPEG_XMP_SIGNATURE.len() + xml.len() + 2;
+    let payload_length: u16 = payload_length
+        .try_into()
+        .map_err(|_| "XMP doesn't fit into a JPEG segment")?;
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&payload_length.to_be_bytes());
+    segment.extend_from_slice(JPEG_XMP_SIGNATURE);
+    segment.extend_from_slice(xml.as_bytes());
+
+    let existing = segments.iter().find(|segment| {
+        segment.marker == 0xE1 && data[segment.start..segment.end()].starts_with(JPEG_XMP_SIGNATURE)
+    });
+    // Segment bounds including the marker and length bytes
+    let (replace_start, replace_end) = match existing {
+        Some(segment) => (segment.start - 4, segment.end()),
+        None => {
+            let after_headers = segments
+                .iter()
+                .take_while(|segment| matches!(segment.marker, 0xE0 | 0xE1))
+                .last()
+                .map(JpegSegment::end)
+                .unwrap_or(2);
+            (after_headers, after_headers)
+        }
+    };
+
+    let mut output = Vec::with_capacity(data.len() + segment.len());
+    output.extend_from_slice(&data[..replace_start]);
+    output.extend_from_slice(&segment);
+    output.extend_from_slice(&data[replace_end..]);
+
+    let temp_path = path.with_file_name(format!(
+        ".{}.aqiv-tmp",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    std::fs::write(&temp_path, output)?;
+    let result = std::fs::metadata(path)
+        .and_then(|metadata| std::fs::set_permissions(&temp_path, metadata.permissions()))
+        .and_then(|_| std::fs::rename(&temp_path, path));
+    if let Err(e) = result {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{ImageFormat, RgbImage};
+
+    // Small JPEG with the JFIF header the image crate writes, removed when dropped
+    struct TempJpeg(PathBuf);
+
+    impl TempJpeg {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("aqiv-xmp-{}-{}.jpg", std::process::id(), name));
+            RgbImage::from_fn(16, 8, |x, y| image::Rgb([x as u8 * 16, y as u8 * 32, 128]))
+                .save_with_format(&path, ImageFormat::Jpeg)
+                .unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempJpeg {
+        fn drop(&mut self) {
+            std::fs::remove_file(&self.0).ok();
+        }
+    }
+
+    fn xmp_segment_count(path: &Path) -> usize {
+        let data = std::fs::read(path).unwrap();
+        jpeg_segments(&mut std::io::Cursor::new(&data))
+            .unwrap()
+            .iter()
+            .filter(|segment| {
+                segment.marker == 0xE1
+                    && data[segment.start..segment.end()].starts_with(JPEG_XMP_SIGNATURE)
+            })
+            .count()
+    }
+
+    fn assert_decodes(path: &Path) {
+        let image = image::open(path).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+    }
+
+    #[test]
+    fn reads_attribute_and_element_values() {
+        let attribute = r#"<rdf:Description xmp:Rating="3" xmp:Label='Red'/>"#;
+        assert_eq!(get_value(attribute, "xmp:Rating").as_deref(), Some("3"));
+        assert_eq!(get_value(attribute, "xmp:Label").as_deref(), Some("Red"));
+
+        let element = "<rdf:Description>\n <xmp:Rating> 4 </xmp:Rating>\n</rdf:Description>";
+        assert_eq!(get_value(element, "xmp:Rating").as_deref(), Some("4"));
+        assert_eq!(get_value(element, "xmp:Label"), None);
+    }
+
+    #[test]
+    fn name_has_to_match_whole() {
+        let xml = r#"<rdf:Description xmp:RatingPercent="60"/>"#;
+        assert_eq!(get_value(xml, "xmp:Rating"), None);
+
+        let xml = r#"<rdf:Description xmp:RatingPercent="60" xmp:Rating="3"/>"#;
+        assert_eq!(get_value(xml, "xmp:Rating").as_deref(), Some("3"));
+
+        let changed = set_value(xml.to_string(), "xmp:Rating", "5");
+        assert_eq!(
+            changed,
+            r#"<rdf:Description xmp:RatingPercent="60" xmp:Rating="5"/>"#
+        );
+    }
+
+    #[test]
+    fn replaces_values_in_place() {
+        let attribute = r#"<rdf:Description a="1" xmp:Rating="3" b="2"/>"#;
+        assert_eq!(
+            set_value(attribute.to_string(), "xmp:Rating", "1"),
+            r#"<rdf:Description a="1" xmp:Rating="1" b="2"/>"#
+        );
+
+        let element = "<x><xmp:Rating>3</xmp:Rating><b/></x>";
+        assert_eq!(
+            set_value(element.to_string(), "xmp:Rating", "-1"),
+            "<x><xmp:Rating>-1</xmp:Rating><b/></x>"
+        );
+    }
+
+    #[test]
+    fn inserts_values_and_namespaces() {
+        let xml = set_value(EMPTY_XMP.to_string(), "xmp:Rating", "2");
+        assert_eq!(get_value(&xml, "xmp:Rating").as_deref(), Some("2"));
+        assert_eq!(xml.matches("xmlns:xmp=").count(), 1);
+
+        let xml = set_value(xml, "aqiv:Pick", "1");
+        assert_eq!(get_value(&xml, "aqiv:Pick").as_deref(), Some("1"));
+        assert!(xml.contains(&format!("xmlns:aqiv=\"{}\"", AQIV_NAMESPACE)));
+
+        // Nowhere to put it
+        assert_eq!(set_value(String::from("<x/>"), "xmp:Rating", "1"), "<x/>");
+    }
+
+    #[test]
+    fn adds_xmp_segment_to_jpeg() {
+        let jpeg = TempJpeg::new("add");
+        assert_eq!(read_jpeg_xmp(&jpeg.0), None);
+
+        let xml = set_value(EMPTY_XMP.to_string(), "xmp:Rating", "4");
+        write_jpeg_xmp(&jpeg.0, &xml).unwrap();
+
+        assert_eq!(read_jpeg_xmp(&jpeg.0).as_deref(), Some(xml.as_str()));
+        assert_eq!(xmp_segment_count(&jpeg.0), 1);
+        assert_decodes(&jpeg.0);
+    }
+
+    #[test]
+    fn replaces_xmp_segment_of_jpeg() {
+        let jpeg = TempJpeg::new("replace");
+        let first = set_value(EMPTY_XMP.to_string(), "xmp:Rating", "1");
+        write_jpeg_xmp(&jpeg.0, &first).unwrap();
+
+        // A longer packet moves everything after it
+        let second = set_value(first, "aqiv:Pick", "1");
+        write_jpeg_xmp(&jpeg.0, &second).unwrap();
+
+        assert_eq!(read_jpeg_xmp(&jpeg.0).as_deref(), Some(second.as_str()));
+        assert_eq!(xmp_segment_count(&jpeg.0), 1);
+        assert_decodes(&jpeg.0);
+    }
+
+    #[test]
+    fn embeds_into_jpeg_only_when_asked() {
+        let jpeg = TempJpeg::new("embed");
+        let sidecar = SidecarNaming::FullName.sidecar_path(&jpeg.0);
+
+        let written = write_xmp(&jpeg.0, SidecarNaming::FullName, true, |xml| {
+            set_value(xml, "xmp:Rating", "5")
+        })
+        .unwrap();
+        assert_eq!(written, jpeg.0);
+        assert!(!sidecar.exists());
+        assert_eq!(
+            read_xmp(&jpeg.0).and_then(|xml| get_value(&xml, "xmp:Rating")),
+            Some(String::from("5"))
+        );
+        assert_decodes(&jpeg.0);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::app::App;
+
+use egui::{TextureOptions, Vec2};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ZoomMode {
+    Custom,
+    Fit,
+    Fill,
+    ActualPixels,
+    FitWidth,
+    FitHeight,
+}
+
+impl ZoomMode {
+    pub const SELECTABLE: [ZoomMode; 5] = [
+        ZoomMode::Fit,
+        ZoomMode::Fill,
+        ZoomMode::ActualPixels,
+        ZoomMode::FitWidth,
+        ZoomMode::FitHeight,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::SELECTABLE
+            .iter()
+            .position(|m| *m == self)
+            .map(|i| i + 1)
+            .unwrap_or_default();
+        Self::SELECTABLE[index % Self::SELECTABLE.len()]
+    }
+}
+
+impl fmt::Display for ZoomMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ZoomMode::Custom => "Custom",
+            ZoomMode::Fit => "Fit to window",
+            ZoomMode::Fill => "Fill window",
+            ZoomMode::ActualPixels => "Actual pixels",
+            ZoomMode::FitWidth => "Fit width",
+            ZoomMode::FitHeight => "Fit height",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl App {
+    // Image size as it appears on screen, i.e. with width and height swapped when rotated
+    pub fn oriented_image_size(&self) -> Option<Vec2> {
+        let size = self.image_state.image_size()?;
+
+        match [1u8, 3u8].contains(&self.image_state.rotation) {
+            true => Some(Vec2::new(size.y, size.x)),
+            false => Some(size),
+        }
+    }
+
+    // zoom_factor is relative to the fitted size, so every mode is converted
+    // from "screen points per image pixel" back into that scale
+    pub fn apply_zoom_mode(&mut self) {
+        let Some(image_size) = self.oriented_image_size() else {
+            return;
+        };
+        let window_size = self.app_state.window_size;
+        let fit_scale = (window_size / image_size).min_elem();
+
+        let scale = match self.image_state.zoom_mode {
+            ZoomMode::Custom => return,
+            ZoomMode::Fit => fit_scale,
+            ZoomMode::Fill => (window_size / image_size).max_elem(),
+            ZoomMode::ActualPixels => 1.0 / self.app_state.pixels_per_point,
+            ZoomMode::FitWidth => window_size.x / image_size.x,
+            ZoomMode::FitHeight => window_size.y / image_size.y,
+        };
+
+        if fit_scale > 0.0 {
+            self.image_state.zoom_factor = scale / fit_scale;
+        }
+    }
+
+    pub fn set_zoom_mode(&mut self, mode: ZoomMode) {
+        self.image_state.zoom_mode = mode;
+        self.image_state.reset_offset();
+        self.apply_zoom_mode();
+        self.notify_zoom();
+    }
+
+    pub fn cycle_zoom_mode(&mut self) {
+        self.set_zoom_mode(self.image_state.zoom_mode.next());
+    }
+
+    // Zoom in physical pixels per image pixel
+    pub fn zoom_percent(&self) -> f32 {
+        let (Some(drawn_rect), Some(image_size)) =
+            (self.calculate_drawn_rect(), self.image_state.image_size())
+        else {
+            return 0.0;
+        };
+
+        drawn_rect.width() / image_size.x * self.app_state.pixels_per_point * 100.0
+    }
+
+    // Fitted zoom can't go below 10% of the window, and may go as far as
+    // max_zoom_percent of real image pixels (at least the old 10x window cap)
+    pub fn clamp_zoom_factor(&self, zoom_factor: f32) -> f32 {
+        let percent_per_zoom_factor = match self.oriented_image_size() {
+            Some(image_size) => {
+                (self.app_state.window_size / image_size).min_elem()
+                    * self.app_state.pixels_per_point
+                    * 100.0
+            }
+            None => 0.0,
+        };
+
+        let max_zoom_factor = match percent_per_zoom_factor > 0.0 {
+            true => (self.app_state.max_zoom_percent / percent_per_zoom_factor).max(10.0),
+            false => 10.0,
+        };
+
+        zoom_factor.clamp(0.1, max_zoom_factor)
+    }
+
+    // Smooth filtering blurs magnified pixel art, so switch to nearest above the threshold
+    pub fn update_texture_filter(&mut self) {
+        let texture_options =
+            match self.zoom_percent() >= self.app_state.nearest_filter_zoom_percent {
+                true => TextureOptions::NEAREST,
+                false => TextureOptions::LINEAR,
+            };
+
+        self.image_state.set_texture_options(texture_options);
+    }
+
+    pub fn notify_zoom(&mut self) {
+        let message = format!(
+            "Zoom: {:.0}% ({})",
+            self.zoom_percent(),
+            self.image_state.zoom_mode
+        );
+        self.app_state.notify(message);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::app::image_utils::load_image;
+
+use clap::builder::PossibleValuesParser;
+use image::{
+    DynamicImage, ImageFormat,
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder},
+    imageops::FilterType,
+};
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args, Debug)]
+pub struct ConvertArgs {
+    /// Input images. Without --output the last one is the output file
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    /// Write every input into this directory
+    #[arg(short, long, value_name = "DIR")]
+    output: Option<PathBuf>,
+
+    /// Output format extension, e.g. png, jpg, webp, avif
+    #[arg(long, value_name = "EXT")]
+    format: Option<String>,
+
+    /// Fit into WIDTHxHEIGHT keeping the aspect ratio, either side may be omitted
+    #[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_resize)]
+    resize: Option<(Option<u32>, Option<u32>)>,
+
+    /// Rotate clockwise by this many degrees
+    #[arg(long, value_name = "DEGREES", value_parser = PossibleValuesParser::new(["90", "180", "270"]))]
+    rotate: Option<String>,
+
+    /// Encoder quality for JPEG and AVIF
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: Option<u8>,
+}
+
+fn parse_resize(value: &str) -> Result<(Option<u32>, Option<u32>), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| String::from("expected WIDTHxHEIGHT, e.g. 1920x1080, 1920x or x1080"))?;
+
+    let parse_side = |side: &str| match side {
+        "" => Ok(None),
+        _ => side
+            .parse::<u32>()
+            .ok()
+            .filter(|side| *side > 0)
+            .map(Some)
+            .ok_or_else(|| format!("invalid size: {}", side)),
+    };
+
+    match (parse_side(width)?, parse_side(height)?) {
+        (None, None) => Err(String::from("at least one side is required")),
+        size => Ok(size),
+    }
+}
+
+fn output_format(extension: &str) -> Result<ImageFormat, Box<dyn Error>> {
+    match ImageFormat::from_extension(extension) {
+        Some(format) if format.writing_enabled() => Ok(format),
+        _ => Err(format!("Can't encode {} files, pick another --format", extension).into()),
+    }
+}
+
+fn extension_of(path: &Path) -> &str {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+}
+
+// --format wins over the extension of a single output file
+fn format_extension<'a>(output: &'a Path, args: &'a ConvertArgs) -> &'a str {
+    args.format
+        .as_deref()
+        .unwrap_or_else(|| extension_of(output))
+}
+
+// Only JPEG and AVIF encoders take a quality. Checked before anything is written,
+// so a batch doesn't stop halfway over it
+fn check_quality(pairs: &[(PathBuf, PathBuf)], args: &ConvertArgs) -> Result<(), Box<dyn Error>> {
+    if args.quality.is_none() {
+        return Ok(());
+    }
+
+    for (_, output) in pairs {
+        let extension = format_extension(output, args);
+        // Formats that can't be written at all are reported for their files
+        let Ok(format) = output_format(extension) else {
+            continue;
+        };
+        if !matches!(format, ImageFormat::Jpeg | ImageFormat::Avif) {
+            return Err(format!(
+                "--quality only applies to JPEG and AVIF, not to {} files",
+                extension
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+// Pairs every input with the file it is written to
+fn output_paths(args: &ConvertArgs) -> Result<Vec<(PathBuf, PathBuf)>, Box<dyn Error>> {
+    let Some(dir) = &args.output else {
+        let [input, output] = args.files.as_slice() else {
+            return Err(
+                "Pass an input and an output file, or --output DIR for several inputs".into(),
+            );
+        };
+        return Ok(vec![(input.clone(), output.clone())]);
+    };
+
+    let pairs: Vec<(PathBuf, PathBuf)> = args
+        .files
+        .iter()
+        .map(|input| {
+            let extension = args
+                .format
+                .as_deref()
+                .unwrap_or_else(|| extension_of(input));
+            // Appended rather than with_extension, which would cut "two.tar" down to "two"
+            let mut name = input.file_stem().unwrap_or_default().to_os_string();
+            name.push(".");
+            name.push(extension);
+            (input.clone(), dir.join(name))
+        })
+        .collect();
+
+    // photo.png and photo.jpg would both become photo.webp, refuse before writing anything
+    let mut outputs = HashMap::new();
+    for (input, output) in &pairs {
+        if let Some(other) = outputs.insert(output, input) {
+            return Err(format!(
+                "{} and {} would both be written to {}",
+                other.display(),
+                input.display(),
+                output.display()
+            )
+            .into());
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn transform(mut image: DynamicImage, args: &ConvertArgs) -> DynamicImage {
+    image = match args.rotate.as_deref() {
+        Some("90") => image.rotate90(),
+        Some("180") => image.rotate180(),
+        Some("270") => image.rotate270(),
+        _ => image,
+    };
+
+    if let Some((width, height)) = args.resize {
+        image = image.resize(
+            width.unwrap_or(u32::MAX),
+            height.unwrap_or(u32::MAX),
+            FilterType::Lanczos3,
+        );
+    }
+
+    image
+}
+
+fn convert_file(input: &PathBuf, output: &Path, args: &ConvertArgs) -> Result<(), Box<dyn Error>> {
+    let format = output_format(format_extension(output, args))?;
+
+    let (_, image) = load_image(input)?;
+    let image = transform(image, args);
+
+    match (format, args.quality) {
+        (ImageFormat::Jpeg, Some(quality)) => {
+            let writer = BufWriter::new(File::create(output)?);
+            image.write_with_encoder(JpegEncoder::new_with_quality(writer, quality))?
+        }
+        (ImageFormat::Avif, Some(quality)) => {
+            let writer = BufWriter::new(File::create(output)?);
+            image.write_with_encoder(AvifEncoder::new_with_speed_quality(writer, 4, quality))?
+        }
+        _ => image.save_with_format(output, format)?,
+    }
+
+    Ok(())
+}
+
+// Decodes through the viewer's loader chain and encodes with the image crate.
+// A failed file doesn't stop the batch, but makes the exit code non-zero
+pub fn convert(args: &ConvertArgs) -> Result<(), Box<dyn Error>> {
+    let pairs = output_paths(args)?;
+    check_quality(&pairs, args)?;
+    if let Some(dir) = &args.output {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut failed = false;
+    for (input, output) in pairs {
+        match convert_file(&input, &output, args) {
+            Ok(()) => println!("{} -> {}", input.display(), output.display()),
+            Err(e) => {
+                eprintln!("{}: {}", input.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(files: &[&str], output: Option<&str>, format: Option<&str>) -> ConvertArgs {
+        ConvertArgs {
+            files: files.iter().map(PathBuf::from).collect(),
+            output: output.map(PathBuf::from),
+            format: format.map(String::from),
+            resize: None,
+            rotate: None,
+            quality: None,
+        }
+    }
+
+    fn pair(input: &str, output: &str) -> (PathBuf, PathBuf) {
+        (PathBuf::from(input), PathBuf::from(output))
+    }
+
+    #[test]
+    fn parses_resize() {
+        assert_eq!(parse_resize("1920x1080"), Ok((Some(1920), Some(1080))));
+        assert_eq!(parse_resize("1920x"), Ok((Some(1920), None)));
+        assert_eq!(parse_resize("x1080"), Ok((None, Some(1080))));
+    }
+
+    #[test]
+    fn rejects_invalid_resize() {
+        for value in ["x", "1920", "0x1080", "1920x-1", "widexhigh", ""] {
+            assert!(parse_resize(value).is_err(), "{} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn pairs_input_with_output_file() {
+        let pairs = output_paths(&args(&["in.jxl", "out.png"], None, None)).unwrap();
+        assert_eq!(pairs, [pair("in.jxl", "out.png")]);
+
+        assert!(output_paths(&args(&["in.jxl"], None, None)).is_err());
+        assert!(output_paths(&args(&["a.jxl", "b.jxl", "out.png"], None, None)).is_err());
+    }
+
+    #[test]
+    fn writes_into_output_directory() {
+        let pairs = output_paths(&args(&["a/one.jxl", "b/two.png"], Some("out"), None)).unwrap();
+        assert_eq!(
+            pairs,
+            [
+                pair("a/one.jxl", "out/one.jxl"),
+                pair("b/two.png", "out/two.png")
+            ]
+        );
+
+        let pairs = output_paths(&args(
+            &["one.jxl", "two.tar.png"],
+            Some("out"),
+            Some("webp"),
+        ))
+        .unwrap();
+        assert_eq!(
+            pairs,
+            [
+                pair("one.jxl", "out/one.webp"),
+                pair("two.tar.png", "out/two.tar.webp")
+            ]
+        );
+    }
+
+    #[test]
+    fn refuses_outputs_written_twice() {
+        let args = args(&["a/photo.png", "b/photo.jpg"], Some("out"), Some("webp"));
+        assert!(output_paths(&args).is_err());
+    }
+
+    #[test]
+    fn quality_only_for_jpeg_and_avif() {
+        let mut args = args(&["in.png", "out.png"], None, None);
+        let pairs = output_paths(&args).unwrap();
+        assert!(check_quality(&pairs, &args).is_ok());
+
+        args.quality = Some(80);
+        assert!(check_quality(&pairs, &args).is_err());
+
+        args.format = Some(String::from("jpg"));
+        assert!(check_quality(&pairs, &args).is_ok());
+
+        let pairs = [pair("in.png", "out.avif")];
+        args.format = None;
+        assert!(check_quality(&pairs, &args).is_ok());
+    }
+}
@@ -0,0 +1,97 @@
+use crate::app::image_utils::{load_image, metadata};
+use crate::app::utils::convert_size;
+
+use serde::Serialize;
+
+use std::{collections::BTreeMap, error::Error, path::PathBuf};
+
+#[derive(Serialize)]
+struct InfoReport {
+    path: PathBuf,
+    format: String,
+    loader: String,
+    width: u32,
+    height: u32,
+    size: u64,
+    color_type: String,
+    bits_per_channel: u16,
+    has_alpha: bool,
+    exif: BTreeMap<String, String>,
+}
+
+fn report(file: &PathBuf) -> Result<InfoReport, Box<dyn Error>> {
+    let (img_info, image) = load_image(file)?;
+    let (width, height) = img_info.resolution.unwrap_or_default();
+    let color = metadata::color_info(&img_info.path, &image);
+
+    Ok(InfoReport {
+        exif: metadata::read_exif(&img_info.path),
+        path: img_info.path,
+        format: img_info.format,
+        loader: img_info.loader,
+        width,
+        height,
+        size: img_info.size,
+        color_type: color.color_type,
+        bits_per_channel: color.bits_per_channel,
+        has_alpha: color.has_alpha,
+    })
+}
+
+fn print_report(report: &InfoReport) {
+    println!("File:        {}", report.path.display());
+    println!("Format:      {}", report.format);
+    println!("Loader:      {}", report.loader);
+    println!("Dimensions:  {} x {}", report.width, report.height);
+    println!(
+        "File size:   {} ({} bytes)",
+        convert_size(report.size as f64),
+        report.size
+    );
+    println!(
+        "Color type:  {} ({} bits per channel)",
+        report.color_type, report.bits_per_channel
+    );
+    println!("Alpha:       {}", report.has_alpha);
+
+    if !report.exif.is_empty() {
+        println!("EXIF:");
+        for (tag, value) in &report.exif {
+            println!("  {}: {}", tag, value);
+        }
+    }
+}
+
+// Prints what the viewer knows about every file. JSON is always an array, whatever
+// the number of files. Files that can't be opened are reported on stderr
+pub fn info(files: &[PathBuf], json: bool) -> Result<(), Box<dyn Error>> {
+    let mut reports = Vec::new();
+    let mut failed = false;
+
+    for file in files {
+        match report(file) {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                eprintln!("{}: {}", file.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for (index, report) in reports.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            print_report(report);
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
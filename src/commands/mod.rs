@@ -0,0 +1,7 @@
+mod convert;
+mod info;
+mod thumbnail;
+
+pub use convert::{ConvertArgs, convert};
+pub use info::info;
+pub use thumbnail::thumbnail;
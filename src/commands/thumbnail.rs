@@ -0,0 +1,12 @@
+use crate::app::image_utils::{
+    load_image,
+    thumbnails::{make_thumbnail, write_thumbnail},
+};
+
+use std::{error::Error, path::Path};
+
+// Entry point for file managers, see aqiv.thumbnailer
+pub fn thumbnail(input: &Path, output: &Path, size: u32) -> Result<(), Box<dyn Error>> {
+    let (info, image) = load_image(&input.to_path_buf())?;
+    write_thumbnail(output, &make_thumbnail(&image, size), &info)
+}
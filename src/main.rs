@@ -1,24 +1,93 @@
 mod app;
+mod commands;
 
 use magick_rust::magick_wand_genesis;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use std::{path::PathBuf, sync::Once};
 
 static START: Once = Once::new();
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(required = false, default_missing_value = "")]
     file_path: Option<String>,
+
+    /// Reopen the last viewed file with the window where it was
+    #[arg(long, conflicts_with = "file_path")]
+    restore: bool,
+
+    /// Open more images next to the first one for comparison
+    #[arg(long, value_name = "FILE", num_args = 1..)]
+    compare: Vec<PathBuf>,
+
+    /// Start in fullscreen
+    #[arg(long, conflicts_with = "borderless")]
+    fullscreen: bool,
+
+    /// Start without window decorations
+    #[arg(long)]
+    borderless: bool,
+
+    /// Write a freedesktop thumbnail of INPUT to the OUTPUT png and exit
+    #[arg(long, num_args = 2, value_names = ["INPUT", "OUTPUT"])]
+    thumbnail: Option<Vec<PathBuf>>,
+
+    /// Longest side of the thumbnail in pixels
+    #[arg(
+        short,
+        long,
+        value_name = "N",
+        default_value_t = 256,
+        requires = "thumbnail"
+    )]
+    size: u32,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print format, dimensions, color type and EXIF without opening a window
+    Info {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Print a JSON array instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Convert images between every format the viewer opens and the ones it can encode
+    Convert(commands::ConvertArgs),
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     START.call_once(magick_wand_genesis);
 
     let args = Args::parse();
+
+    match args.command {
+        Some(Command::Info { files, json }) => return commands::info(&files, json),
+        Some(Command::Convert(convert_args)) => return commands::convert(&convert_args),
+        None => (),
+    }
+
+    if let Some([input, output]) = args.thumbnail.as_deref() {
+        return commands::thumbnail(input, output, args.size);
+    }
+
+    let window_mode = if args.fullscreen {
+        app::WindowMode::Fullscreen
+    } else if args.borderless {
+        app::WindowMode::Borderless
+    } else {
+        app::WindowMode::Windowed
+    };
+
     let img_path_arg = args.file_path.unwrap_or_default();
 
     let img_path = if img_path_arg.is_empty() {
@@ -34,5 +103,17 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(_img_path)
     };
 
-    app::run(img_path)
+    let compare_paths: Vec<PathBuf> = args
+        .compare
+        .iter()
+        .map(|path| match path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => {
+                println!("File {:?} does not exist!", path);
+                std::process::exit(1);
+            }
+        })
+        .collect();
+
+    app::run(img_path, compare_paths, window_mode, args.restore)
 }